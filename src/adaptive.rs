@@ -0,0 +1,183 @@
+//! Closed-loop adaptive request rate, modeled on delay-based congestion control.
+//!
+//! The pacing in [`crate::requester::spawn_request_loop`]/[`crate::requester::spawn_request_loop_with_timestamp`]
+//! is open-loop: the target rate is fixed (or replayed) up front and never reacts to how the
+//! server is actually behaving. [`AdaptiveRateController`] instead groups completed-request
+//! latencies into fixed windows, fits an OLS line over a sliding history of the smoothed
+//! per-window latency, and nudges the target rate down on a clearly positive slope (queue
+//! buildup) or up on a near-zero slope (headroom) -- the same idea TCP Vegas uses for congestion
+//! windows. [`crate::requester::spawn_adaptive_request_loop`] feeds it observed latencies and
+//! reads back the target rate every request.
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveRateConfig {
+    /// Width of one latency-smoothing window.
+    pub window: Duration,
+    /// Number of smoothed per-window latencies kept for the OLS fit.
+    pub history_len: usize,
+    /// Slope (seconds of latency growth per window) above which the controller treats the trend
+    /// as queue buildup and backs off.
+    pub slope_threshold: f64,
+    pub min_rate: f64,
+    pub max_rate: f64,
+    /// Multiplicative backoff applied to the rate on overload.
+    pub decrease_factor: f64,
+    /// Additive step applied to the rate when there is headroom.
+    pub increase_step: f64,
+}
+
+impl Default for AdaptiveRateConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_millis(200),
+            history_len: 40,
+            slope_threshold: 0.01,
+            min_rate: 0.5,
+            max_rate: 1000.0,
+            decrease_factor: 0.85,
+            increase_step: 0.5,
+        }
+    }
+}
+
+struct State {
+    rate: f64,
+    window_start: Instant,
+    window_samples: Vec<f64>,
+    history: VecDeque<f64>,
+    trajectory: Vec<f64>,
+}
+
+/// Delay-based rate controller shared between the request-dispatch loop (reads [`target_rate`])
+/// and the per-request completion handler (feeds [`observe`]).
+///
+/// [`target_rate`]: AdaptiveRateController::target_rate
+/// [`observe`]: AdaptiveRateController::observe
+pub struct AdaptiveRateController {
+    config: AdaptiveRateConfig,
+    state: Mutex<State>,
+}
+
+impl AdaptiveRateController {
+    pub fn new(config: AdaptiveRateConfig, initial_rate: f64) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(State {
+                rate: initial_rate.clamp(config.min_rate, config.max_rate),
+                window_start: Instant::now(),
+                window_samples: Vec::new(),
+                history: VecDeque::with_capacity(config.history_len),
+                trajectory: Vec::new(),
+            }),
+            config,
+        })
+    }
+
+    /// Current target request rate (req/s).
+    pub fn target_rate(&self) -> f64 {
+        self.state.lock().unwrap().rate
+    }
+
+    /// Record one completed request's end-to-end latency (seconds). Once `window` has elapsed
+    /// since the last adjustment, the buffered samples are averaged into the history and the rate
+    /// is re-tuned from the OLS slope of that history.
+    pub fn observe(&self, latency_secs: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.window_samples.push(latency_secs);
+
+        if state.window_start.elapsed() < self.config.window {
+            return;
+        }
+
+        let smoothed = state.window_samples.iter().sum::<f64>() / state.window_samples.len() as f64;
+        state.window_samples.clear();
+        state.window_start = Instant::now();
+
+        state.history.push_back(smoothed);
+        if state.history.len() > self.config.history_len {
+            state.history.pop_front();
+        }
+
+        if state.history.len() >= 3 {
+            let slope = ols_slope(&state.history);
+            if slope > self.config.slope_threshold {
+                state.rate *= self.config.decrease_factor;
+            } else {
+                state.rate += self.config.increase_step;
+            }
+            state.rate = state.rate.clamp(self.config.min_rate, self.config.max_rate);
+        }
+        state.trajectory.push(state.rate);
+    }
+
+    /// The rate recorded at the end of every completed window, oldest first -- the trajectory the
+    /// controller took to converge on a sustainable rate.
+    pub fn trajectory(&self) -> Vec<f64> {
+        self.state.lock().unwrap().trajectory.clone()
+    }
+}
+
+/// Slope of the ordinary-least-squares line fit to `ys` against the indices `0..ys.len()`.
+fn ols_slope(ys: &VecDeque<f64>) -> f64 {
+    let n = ys.len() as f64;
+    let xs_mean = (n - 1.0) / 2.0;
+    let ys_mean = ys.iter().sum::<f64>() / n;
+
+    let mut numerator = 0.0;
+    let mut denominator = 0.0;
+    for (i, &y) in ys.iter().enumerate() {
+        let dx = i as f64 - xs_mean;
+        numerator += dx * (y - ys_mean);
+        denominator += dx * dx;
+    }
+    if denominator == 0.0 {
+        0.0
+    } else {
+        numerator / denominator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_on_growing_latency() {
+        let config = AdaptiveRateConfig {
+            window: Duration::from_millis(0),
+            history_len: 10,
+            slope_threshold: 0.01,
+            min_rate: 0.5,
+            max_rate: 100.0,
+            decrease_factor: 0.85,
+            increase_step: 0.5,
+        };
+        let controller = AdaptiveRateController::new(config, 10.0);
+        for i in 0..10 {
+            controller.observe(0.1 + i as f64 * 0.05);
+        }
+        assert!(controller.target_rate() < 10.0);
+    }
+
+    #[test]
+    fn ramps_up_on_flat_latency() {
+        let config = AdaptiveRateConfig {
+            window: Duration::from_millis(0),
+            history_len: 10,
+            slope_threshold: 0.01,
+            min_rate: 0.5,
+            max_rate: 100.0,
+            decrease_factor: 0.85,
+            increase_step: 0.5,
+        };
+        let controller = AdaptiveRateController::new(config, 10.0);
+        for _ in 0..10 {
+            controller.observe(0.1);
+        }
+        assert!(controller.target_rate() > 10.0);
+    }
+}
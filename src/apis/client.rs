@@ -0,0 +1,117 @@
+//! Sync/async client split for [`LLMApi`](super::LLMApi) sends, so every API impl gets uniform
+//! retry-with-backoff instead of each `spawn_request_loop*` in [`crate::requester`] reimplementing
+//! it inline.
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use reqwest::Response;
+
+use crate::{
+    auth::EndpointAuth,
+    retry::{with_retry, RetryPolicy},
+};
+
+/// Fires a single request attempt and returns a future without any retry semantics — the raw
+/// one-shot send that [`SyncLLMClient`] wraps with backoff.
+pub trait AsyncLLMClient {
+    fn send(
+        client: &reqwest::Client,
+        endpoint: &str,
+        json_body: String,
+        timeout: Duration,
+        auth: &EndpointAuth,
+    ) -> impl Future<Output = Result<Response, reqwest::Error>> + Send;
+}
+
+/// Outcome of [`SyncLLMClient::send_with_retry`]: the terminal result plus enough per-attempt
+/// bookkeeping that callers can report tail behavior under server overload instead of just a
+/// pass/fail.
+pub struct SyncSendOutcome {
+    pub result: Result<Response, reqwest::Error>,
+    pub attempt_count: u32,
+    pub attempt_latencies_ms: Vec<u64>,
+}
+
+/// Sends and confirms: retries transient failures (connect errors, timeouts, 429/503) per
+/// `policy`, with exponential backoff between attempts. Any other transport error, such as a
+/// connection reset after the request was sent, is returned without a retry.
+///
+/// A blanket impl covers every [`AsyncLLMClient`] for free, the same way
+/// [`crate::protocols::AsyncProtocol`] wraps [`crate::protocols::Protocol`], building on the same
+/// [`with_retry`] backoff loop instead of reimplementing it.
+pub trait SyncLLMClient: AsyncLLMClient {
+    fn send_with_retry(
+        client: &reqwest::Client,
+        endpoint: &str,
+        json_body: String,
+        timeout: Duration,
+        auth: &EndpointAuth,
+        policy: &RetryPolicy,
+    ) -> impl Future<Output = SyncSendOutcome> + Send;
+}
+
+impl<C: AsyncLLMClient> SyncLLMClient for C {
+    async fn send_with_retry(
+        client: &reqwest::Client,
+        endpoint: &str,
+        json_body: String,
+        timeout: Duration,
+        auth: &EndpointAuth,
+        policy: &RetryPolicy,
+    ) -> SyncSendOutcome {
+        // Shared via `RefCell` rather than captured `&mut`, since every future that `with_retry`'s
+        // `FnMut` attempt closure returns holds its own reference to it, which a captured `&mut`
+        // cannot be lent out as; only one returned future is ever alive at a time (each is
+        // awaited to completion before the next attempt starts).
+        let attempt_latencies_ms = std::cell::RefCell::new(Vec::new());
+        let outcome = with_retry(
+            policy,
+            |response: &Response| !policy.is_retryable_status(response.status()),
+            || {
+                let attempt_start = Instant::now();
+                let send = C::send(client, endpoint, json_body.clone(), timeout, auth);
+                let attempt_latencies_ms = &attempt_latencies_ms;
+                async move {
+                    let result = send.await;
+                    attempt_latencies_ms
+                        .borrow_mut()
+                        .push(attempt_start.elapsed().as_millis() as u64);
+                    result
+                }
+            },
+        )
+        .await;
+
+        SyncSendOutcome {
+            result: outcome.result,
+            attempt_count: outcome.retry_count + 1,
+            attempt_latencies_ms: attempt_latencies_ms.into_inner(),
+        }
+    }
+}
+
+/// The plain HTTP [`AsyncLLMClient`]: POSTs `json_body` to `endpoint` with `auth`'s headers,
+/// bounded by `timeout`. Every `LLMApi` sends this way today; a future client (e.g. one that
+/// multiplexes over a persistent connection) only needs to implement [`AsyncLLMClient::send`].
+pub struct HttpLLMClient;
+
+impl AsyncLLMClient for HttpLLMClient {
+    async fn send(
+        client: &reqwest::Client,
+        endpoint: &str,
+        json_body: String,
+        timeout: Duration,
+        auth: &EndpointAuth,
+    ) -> Result<Response, reqwest::Error> {
+        client
+            .post(endpoint)
+            .timeout(timeout)
+            .headers(auth.to_header_map())
+            .body(json_body)
+            .header("Content-Type", "application/json")
+            .send()
+            .await
+    }
+}
@@ -1,6 +1,10 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, time::Instant};
 
+use futures_util::StreamExt;
 use reqwest::Response;
+use serde::Deserialize;
+
+use crate::percentile;
 
 use super::LLMApi;
 
@@ -15,7 +19,7 @@ impl Clone for DistserveApi {
 }
 
 impl LLMApi for DistserveApi {
-    fn request_json_body(&self, prompt: String, output_length: u64) -> String {
+    fn request_json_body(prompt: String, output_length: u64) -> String {
         unimplemented!("this api maybe out dated, checkout for a new one!");
         let json_body = serde_json::json!({
             "prompt": prompt,
@@ -31,7 +35,7 @@ impl LLMApi for DistserveApi {
         json_body.to_string()
     }
 
-    fn parse_response(&self, response: Response) -> BTreeMap<String, String> {
+    async fn parse_response(response: Response) -> BTreeMap<String, String> {
         let mut map = BTreeMap::new();
         map.insert("status".to_string(), response.status().as_str().to_string());
         if response.status().is_success() {
@@ -139,4 +143,98 @@ impl LLMApi for DistserveApi {
         }
         map
     }
+
+    /// Same SSE wire format (`data: {"text": "..."}` chunks) as
+    /// `protocols::DistserveProtocol::parse_response_streaming`, since both talk to the same
+    /// backend; only the `x-*`-header-trusting [`parse_response`](DistserveApi::parse_response)
+    /// above differs between the two subsystems.
+    async fn parse_response_streaming(response: Response) -> BTreeMap<String, String> {
+        let mut map = BTreeMap::new();
+        map.insert("status".to_string(), response.status().as_str().to_string());
+        if !response.status().is_success() {
+            return map;
+        }
+
+        let start = Instant::now();
+        let mut first_token_time = None;
+        let mut last_token_time = start;
+        let mut gaps = Vec::new();
+        let mut output_length = 0u64;
+
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(_) => break,
+            };
+            for line in chunk.split(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(line);
+                let data = line.trim_start_matches("data:").trim();
+                if data.is_empty() || data == "[DONE]" {
+                    continue;
+                }
+
+                let has_text = serde_json::from_str::<StreamToken>(data)
+                    .ok()
+                    .map(|token| !token.text.is_empty())
+                    .unwrap_or(false);
+                if !has_text {
+                    continue;
+                }
+
+                let now = Instant::now();
+                match first_token_time {
+                    None => first_token_time = Some(now.duration_since(start)),
+                    Some(_) => {
+                        gaps.push(now.duration_since(last_token_time).as_secs_f64() * 1000.0)
+                    }
+                }
+                last_token_time = now;
+                output_length += 1;
+            }
+        }
+
+        map.insert(
+            "first_token_time".to_string(),
+            first_token_time
+                .unwrap_or_default()
+                .as_secs_f64()
+                .to_string(),
+        );
+        map.insert(
+            "total_time".to_string(),
+            start.elapsed().as_secs_f64().to_string(),
+        );
+        map.insert("output_length".to_string(), output_length.to_string());
+
+        if !gaps.is_empty() {
+            let mut sorted_gaps = gaps.clone();
+            sorted_gaps.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            map.insert(
+                "max_time_between_tokens".to_string(),
+                sorted_gaps.last().copied().unwrap_or(0.0).to_string(),
+            );
+            map.insert(
+                "p70_time_between_tokens".to_string(),
+                percentile(&sorted_gaps, 0.70).to_string(),
+            );
+            map.insert(
+                "p90_time_between_tokens".to_string(),
+                percentile(&sorted_gaps, 0.90).to_string(),
+            );
+            map.insert(
+                "p99_time_between_tokens".to_string(),
+                percentile(&sorted_gaps, 0.99).to_string(),
+            );
+        }
+
+        map
+    }
 }
+
+#[derive(Deserialize, Debug, Default)]
+struct StreamToken {
+    #[serde(default)]
+    text: String,
+}
+
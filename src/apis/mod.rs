@@ -2,13 +2,31 @@ use std::collections::BTreeMap;
 
 use reqwest::Response;
 
+pub mod client;
 pub mod distserve_api;
+pub mod openai_api;
 pub mod tgi_api;
 
+pub use client::{AsyncLLMClient, HttpLLMClient, SyncLLMClient, SyncSendOutcome};
 pub use distserve_api::DistserveApi;
+pub use openai_api::{OpenAIApi, OpenAIChatApi, OpenAICompletionsApi};
 pub use tgi_api::TGIApi;
 
 pub trait LLMApi: Copy + Clone {
     fn request_json_body(prompt: String, output_length: u64) -> String;
-    fn parse_response(response: Response) -> BTreeMap<String, String>;
+
+    /// Parse a completed response into metrics. Implementors that read the body (e.g. to stream
+    /// SSE chunks and measure timing client-side, as [`OpenAIApi`] does) need the body read, which
+    /// `reqwest` only exposes as `async`, so this is an `async fn` even for implementors that only
+    /// read headers and never actually await anything.
+    async fn parse_response(response: Response) -> BTreeMap<String, String>;
+
+    /// Consume a streaming (SSE) response and measure TTFT/inter-token latency client-side instead
+    /// of trusting server-reported `x-*` headers, which a streaming response may not send. Not
+    /// every implementor supports streaming, so this falls back to `unimplemented!` unless
+    /// overridden.
+    async fn parse_response_streaming(response: Response) -> BTreeMap<String, String> {
+        let _ = response;
+        unimplemented!("{} does not support streaming responses", std::any::type_name::<Self>())
+    }
 }
@@ -0,0 +1,251 @@
+use std::{collections::BTreeMap, time::Instant};
+
+use futures_util::StreamExt;
+use reqwest::Response;
+use serde::Deserialize;
+
+use super::LLMApi;
+
+/// OpenAI-compatible `/v1/completions` API (vLLM, TGI's OpenAI front-end, etc.).
+#[derive(Debug)]
+pub struct OpenAICompletionsApi {}
+
+impl Copy for OpenAICompletionsApi {}
+
+impl Clone for OpenAICompletionsApi {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl LLMApi for OpenAICompletionsApi {
+    fn request_json_body(prompt: String, output_length: u64) -> String {
+        let json_body = serde_json::json!({
+            "prompt": prompt,
+            "max_tokens": output_length,
+            "n": 1,
+            "best_of": 1,
+            "temperature": 1.0,
+            "top_p": 1.0,
+            "ignore_eos": true,
+            "stream": false
+        });
+        json_body.to_string()
+    }
+
+    async fn parse_response(response: Response) -> BTreeMap<String, String> {
+        let mut map = BTreeMap::new();
+        map.insert("status".to_string(), response.status().as_str().to_string());
+        if !response.status().is_success() {
+            return map;
+        }
+
+        let Ok(body) = response.text().await else {
+            return map;
+        };
+        let Ok(parsed) = serde_json::from_str::<CompletionsResponse>(&body) else {
+            return map;
+        };
+        if let Some(usage) = parsed.usage {
+            map.insert("input_length".to_string(), usage.prompt_tokens.to_string());
+            map.insert(
+                "output_length".to_string(),
+                usage.completion_tokens.to_string(),
+            );
+        }
+        map
+    }
+}
+
+/// Standard `/v1/completions` response shape, stripped to the fields this crate cares about
+/// (`choices[].text` isn't needed since `usage` already reports token counts directly).
+#[derive(Deserialize, Debug, Default)]
+struct CompletionsResponse {
+    usage: Option<Usage>,
+}
+
+/// Standard OpenAI `usage` object, shared by completions and chat-completions responses.
+#[derive(Deserialize, Debug, Default)]
+struct Usage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+/// OpenAI-compatible `/v1/chat/completions` API (vLLM, SGLang, TGI's OpenAI front-end, ...),
+/// non-streaming counterpart to [`OpenAIApi`].
+#[derive(Debug)]
+pub struct OpenAIChatApi {}
+
+impl Copy for OpenAIChatApi {}
+
+impl Clone for OpenAIChatApi {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl LLMApi for OpenAIChatApi {
+    fn request_json_body(prompt: String, output_length: u64) -> String {
+        let json_body = serde_json::json!({
+            "messages": [{"role": "user", "content": prompt}],
+            "max_tokens": output_length,
+            "n": 1,
+            "temperature": 1.0,
+            "top_p": 1.0,
+            "ignore_eos": true,
+            "stream": false
+        });
+        json_body.to_string()
+    }
+
+    async fn parse_response(response: Response) -> BTreeMap<String, String> {
+        let mut map = BTreeMap::new();
+        map.insert("status".to_string(), response.status().as_str().to_string());
+        if !response.status().is_success() {
+            return map;
+        }
+
+        let Ok(body) = response.text().await else {
+            return map;
+        };
+        let Ok(parsed) = serde_json::from_str::<ChatCompletionsResponse>(&body) else {
+            return map;
+        };
+        if let Some(usage) = parsed.usage {
+            map.insert("input_length".to_string(), usage.prompt_tokens.to_string());
+            map.insert(
+                "output_length".to_string(),
+                usage.completion_tokens.to_string(),
+            );
+        }
+        map
+    }
+}
+
+/// Standard `/v1/chat/completions` response shape, stripped to the fields this crate cares about
+/// (`choices[].message.content` isn't needed since `usage` already reports token counts directly).
+#[derive(Deserialize, Debug, Default)]
+struct ChatCompletionsResponse {
+    usage: Option<Usage>,
+}
+
+/// One SSE chunk of an OpenAI-compatible `/v1/chat/completions` stream.
+#[derive(Deserialize, Debug, Default)]
+struct ChatStreamChunk {
+    #[serde(default)]
+    choices: Vec<ChatStreamChoice>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ChatStreamChoice {
+    #[serde(default)]
+    delta: ChatStreamDelta,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ChatStreamDelta {
+    #[serde(default)]
+    content: String,
+}
+
+/// OpenAI-compatible streaming chat API (vLLM, SGLang, TGI's OpenAI front-end, ...) for backends
+/// that don't emit TGI's `x-first-token-time`/`x-total-time`/... headers. Requests
+/// `"stream": true` against `/v1/chat/completions` and measures time-to-first-token and
+/// inter-token latency purely from locally observed SSE arrivals instead of trusting headers, the
+/// same way `DistserveProtocol::parse_response_streaming` does for the `protocols` subsystem.
+#[derive(Debug)]
+pub struct OpenAIApi {}
+
+impl Copy for OpenAIApi {}
+
+impl Clone for OpenAIApi {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl LLMApi for OpenAIApi {
+    fn request_json_body(prompt: String, output_length: u64) -> String {
+        let json_body = serde_json::json!({
+            "messages": [{"role": "user", "content": prompt}],
+            "max_tokens": output_length,
+            "n": 1,
+            "temperature": 1.0,
+            "top_p": 1.0,
+            "stream": true
+        });
+        json_body.to_string()
+    }
+
+    async fn parse_response(response: Response) -> BTreeMap<String, String> {
+        let mut map = BTreeMap::new();
+        map.insert("status".to_string(), response.status().as_str().to_string());
+        if !response.status().is_success() {
+            return map;
+        }
+
+        let start = Instant::now();
+        let mut first_token_time = None;
+        let mut last_token_time = start;
+        let mut gaps = Vec::new();
+        let mut output_length = 0u64;
+
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(_) => break,
+            };
+            for line in chunk.split(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(line);
+                let data = line.trim_start_matches("data:").trim();
+                if data.is_empty() || data == "[DONE]" {
+                    continue;
+                }
+
+                let has_text = serde_json::from_str::<ChatStreamChunk>(data)
+                    .ok()
+                    .and_then(|chunk| chunk.choices.into_iter().next())
+                    .map(|choice| !choice.delta.content.is_empty())
+                    .unwrap_or(false);
+                if !has_text {
+                    continue;
+                }
+
+                let now = Instant::now();
+                match first_token_time {
+                    None => first_token_time = Some(now.duration_since(start)),
+                    Some(_) => {
+                        gaps.push(now.duration_since(last_token_time).as_secs_f64() * 1000.0)
+                    }
+                }
+                last_token_time = now;
+                output_length += 1;
+            }
+        }
+
+        map.insert(
+            "first_token_time".to_string(),
+            first_token_time
+                .unwrap_or_default()
+                .as_secs_f64()
+                .to_string(),
+        );
+        map.insert(
+            "inference_time".to_string(),
+            start.elapsed().as_secs_f64().to_string(),
+        );
+        map.insert("output_length".to_string(), output_length.to_string());
+
+        if !gaps.is_empty() {
+            let max_gap = gaps.iter().copied().fold(0.0_f64, f64::max);
+            let mean_gap = gaps.iter().sum::<f64>() / gaps.len() as f64;
+            map.insert("max_time_between_tokens".to_string(), max_gap.to_string());
+            map.insert("mean_time_between_tokens".to_string(), mean_gap.to_string());
+        }
+
+        map
+    }
+}
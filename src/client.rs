@@ -1,17 +1,40 @@
-use std::{pin::Pin, sync::Arc};
+use std::{pin::Pin, sync::Arc, time::Duration};
 
 use clap::Parser;
 use request_sim::{
-    apis::TGIApi,
-    dataset::{AzureDataset, BailianDataset, LLMTrace, MooncakeDataset},
+    adaptive::{AdaptiveRateConfig, AdaptiveRateController},
+    apis::{OpenAICompletionsApi, TGIApi},
+    auth::{Auth, EndpointAuth},
+    dataset::{AzureDataset, BailianDataset, GenericTrace, LLMTrace, MooncakeDataset},
+    dispatch::Dispatcher,
+    protocols::{DistserveProtocol, StProtocol, TgiProtocol, VllmProtocol},
+    rate_limiter::{RateLimiter, RateLimiterConfig},
     requester::{
-        create_gamma_interval_generator, report_loop, spawn_request_loop,
-        spawn_request_loop_with_timestamp,
+        create_gamma_interval_generator, report_loop, report_loop_with_admin,
+        report_loop_with_tui, spawn_adaptive_request_loop, spawn_dispatcher_request_loop_with_timestamp,
+        spawn_protocol_batch_request_loop_with_timestamp, spawn_protocol_request_loop_with_timestamp,
+        spawn_request_loop, spawn_request_loop_with_timestamp,
     },
+    retry::RetryPolicy,
     token_sampler::TokenSampler,
+    trace_schema::TraceSchema,
 };
 use tokenizers::Tokenizer;
-use tokio::{spawn, sync::broadcast};
+use tokio::{
+    spawn,
+    sync::{broadcast, oneshot},
+};
+
+/// clap `value_parser` for `--rate-limit`: rejects zero/negative up front instead of letting
+/// `RateLimiter::new` divide by zero computing its refill interval.
+fn parse_positive_rate(raw: &str) -> Result<f64, String> {
+    let rate: f64 = raw.parse().map_err(|err| format!("invalid rate: {err}"))?;
+    if rate > 0.0 {
+        Ok(rate)
+    } else {
+        Err(format!("--rate-limit must be positive, got {rate}"))
+    }
+}
 
 #[derive(Parser)]
 struct Args {
@@ -39,7 +62,23 @@ struct Args {
     #[clap(long, required = true)]
     endpoint: String,
 
-    /// LLM API server type. Either "tgi" (text-generation-inference), or "distserve"
+    /// LLM API server type. Either "tgi" (text-generation-inference) or "openai"
+    /// (OpenAI-compatible `/v1/completions`, e.g. vLLM).
+    ///
+    /// The "-protocol" suffixed variants ("tgi-protocol", "distserve-protocol", "vllm-protocol",
+    /// "st-protocol") route through `request_sim::protocols` instead: the request body is sized
+    /// directly from sampled token counts rather than a decoded prompt string, at the cost of not
+    /// supporting `--adaptive` or `--auth-*` yet.
+    ///
+    /// The "-dispatch" suffixed variants ("tgi-dispatch", "openai-dispatch") fan out onto a bounded
+    /// `request_sim::dispatch::Dispatcher` worker pool (see `--dispatch-workers`/
+    /// `--dispatch-queue-capacity`) instead of one `tokio::spawn` per request, at the same cost of
+    /// not supporting `--adaptive` yet.
+    ///
+    /// The "-protocol-batch" suffixed variants ("tgi-protocol-batch", "vllm-protocol-batch")
+    /// coalesce up to `--max-client-batch-size` pending dataset entries into one native-batch
+    /// request via `Protocol::request_json_body_batched`/`parse_response_batched` instead of one
+    /// request per entry. Only protocols that override those methods support this.
     #[clap(long, short, required = true)]
     api: String,
 
@@ -59,6 +98,29 @@ struct Args {
     #[clap(long)]
     second_dataset_path: Option<String>,
 
+    /// Path to a JSON [`request_sim::trace_schema::TraceSchema`] config file. Required only when
+    /// `--dataset generic` is used; see [`request_sim::trace_schema::TraceSchema::from_json_file`].
+    #[clap(long)]
+    schema_path: Option<String>,
+
+    /// Block size used to dedupe repeated prompt prefixes (see the `hash_ids` field of
+    /// [`request_sim::trace_schema::TraceSchema`]). Only takes effect for `--dataset generic`;
+    /// the other dataset types use a block size fixed to their own known format.
+    #[clap(long, default_value_t = 16)]
+    generic_block_size: usize,
+
+    /// Memory-map the `--dataset generic` trace file and index it lazily instead of reading every
+    /// record into memory up front. Use for traces too large to fit comfortably in memory.
+    #[clap(long, default_value_t = false)]
+    streaming: bool,
+
+    /// Skip and count records that fail the `--dataset generic` schema's conversion instead of
+    /// failing the whole load (see [`request_sim::dataset::GenericTrace::load_tolerant`]). Only
+    /// takes effect for `--dataset generic`; overrides `--streaming`, since tolerant loading reads
+    /// every record eagerly to decide what to skip.
+    #[clap(long, default_value_t = false)]
+    tolerant_load: bool,
+
     /// If the `replay_mode` is enabled, the client will send requests following
     /// the sequence and input/output length of provided dataset above.
     ///
@@ -90,6 +152,96 @@ struct Args {
     /// Requester run time.
     #[clap(long, short, default_value_t = 60)]
     time_in_secs: u64,
+
+    /// Render a live terminal dashboard (request rate, in-flight/completed counts, latency
+    /// percentiles) instead of writing only to `output_path`.
+    #[clap(long, default_value_t = false)]
+    tui: bool,
+
+    /// Serve live aggregates (request counts, in-flight gauge, latency percentiles) over HTTP at
+    /// this address, e.g. "0.0.0.0:9090" (`/metrics` in Prometheus text format, `/stats` as JSON),
+    /// for the duration of the run. Mutually exclusive with `--tui`.
+    #[clap(long)]
+    admin_addr: Option<std::net::SocketAddr>,
+
+    /// Closed-loop adaptive rate: instead of a fixed/replayed rate, continuously re-tune the
+    /// target RPS from the trend of observed end-to-end latency (see
+    /// `request_sim::adaptive::AdaptiveRateController`). `request_rate` (default 1.0) only sets
+    /// the starting rate; `scale_factor` is ignored.
+    #[clap(long, default_value_t = false)]
+    adaptive: bool,
+
+    /// Width, in milliseconds, of one latency-smoothing window for `--adaptive`.
+    #[clap(long, default_value_t = 200)]
+    adaptive_window_ms: u64,
+
+    /// Number of smoothed per-window latencies kept for the `--adaptive` OLS slope fit.
+    #[clap(long, default_value_t = 40)]
+    adaptive_history_len: usize,
+
+    /// Slope (seconds of latency growth per window) above which `--adaptive` backs off the rate.
+    #[clap(long, default_value_t = 0.01)]
+    adaptive_slope_threshold: f64,
+
+    /// Lower bound on the `--adaptive` target rate (req/s).
+    #[clap(long, default_value_t = 0.5)]
+    adaptive_min_rate: f64,
+
+    /// Upper bound on the `--adaptive` target rate (req/s).
+    #[clap(long, default_value_t = 1000.0)]
+    adaptive_max_rate: f64,
+
+    /// Number of retries on a retryable (e.g. 429/503) response, with exponential backoff between
+    /// attempts. Applies to every `--api` type while `--adaptive` is disabled; `--adaptive` runs
+    /// ignore it.
+    #[clap(long, default_value_t = 0)]
+    retries: u8,
+
+    /// Fraction of the per-second token budget the client-side rate limiter may accumulate as
+    /// burst capacity, e.g. `0.2` allows bursting 20% above the steady drip rate. Only takes
+    /// effect when `--rate-limit` is set.
+    #[clap(long, default_value_t = 0.2)]
+    burst_pct: f64,
+
+    /// Cap the client on a token-bucket rate limiter (req/s), independent of `--request-rate`'s
+    /// pacing, to stay safely under a server's hard admission limit. Applies to every `--api` type
+    /// while `--adaptive` is disabled; `--adaptive` runs ignore it. Must be positive: zero or
+    /// negative would make `RateLimiter::new` divide by zero (or flip the bucket's math) while
+    /// computing its refill interval.
+    #[clap(long, value_parser = parse_positive_rate)]
+    rate_limit: Option<f64>,
+
+    /// `Authorization: Bearer <token>` on every request. Mutually exclusive with
+    /// `--auth-env-var`/`--auth-header`; precedence when more than one is set is
+    /// bearer-token > auth-env-var > auth-header.
+    #[clap(long)]
+    bearer_token: Option<String>,
+
+    /// Name of an environment variable holding a bearer token, read at request time instead of
+    /// passed as a CLI argument, so it never ends up in shell history or `ps`. Mutually
+    /// exclusive with `--bearer-token`/`--auth-header`.
+    #[clap(long)]
+    auth_env_var: Option<String>,
+
+    /// An arbitrary `name:value` header to attach to every request, e.g. `x-api-key:secret`.
+    /// Mutually exclusive with `--bearer-token`/`--auth-env-var`.
+    #[clap(long)]
+    auth_header: Option<String>,
+
+    /// Worker OS threads behind `--api *-dispatch` variants. Only takes effect for those variants.
+    #[clap(long, default_value_t = 16)]
+    dispatch_workers: usize,
+
+    /// Bounded queue depth behind `--api *-dispatch` variants. Only takes effect for those
+    /// variants; once full, `Dispatcher::try_submit` sheds load instead of blocking the pacing
+    /// loop (see `request_sim::dispatch::DispatchStats::dropped`).
+    #[clap(long, default_value_t = 256)]
+    dispatch_queue_capacity: usize,
+
+    /// Maximum number of pending dataset entries coalesced into one request behind
+    /// `--api *-protocol-batch` variants. Only takes effect for those variants.
+    #[clap(long, default_value_t = 8)]
+    max_client_batch_size: usize,
 }
 
 async fn async_main(args: Args) -> Result<(), i32> {
@@ -104,6 +256,10 @@ async fn async_main(args: Args) -> Result<(), i32> {
         dataset,
         dataset_path,
         second_dataset_path,
+        schema_path,
+        generic_block_size,
+        streaming,
+        tolerant_load,
         replay_mode,
         scale_replay_path,
         request_rate,
@@ -111,8 +267,41 @@ async fn async_main(args: Args) -> Result<(), i32> {
         cv,
         output_path,
         time_in_secs,
+        tui,
+        admin_addr,
+        adaptive,
+        adaptive_window_ms,
+        adaptive_history_len,
+        adaptive_slope_threshold,
+        adaptive_min_rate,
+        adaptive_max_rate,
+        retries,
+        burst_pct,
+        rate_limit,
+        bearer_token,
+        auth_env_var,
+        auth_header,
+        dispatch_workers,
+        dispatch_queue_capacity,
+        max_client_batch_size,
     } = args;
 
+    let endpoint_auth = if let Some(token) = bearer_token {
+        EndpointAuth::new(Auth::Bearer(token))
+    } else if let Some(env_var) = auth_env_var {
+        EndpointAuth::new(Auth::BearerEnv(env_var))
+    } else if let Some(header) = auth_header {
+        let (name, value) = header
+            .split_once(':')
+            .expect("--auth-header must be in NAME:VALUE form");
+        EndpointAuth::new(Auth::Header {
+            name: name.to_string(),
+            value: value.to_string(),
+        })
+    } else {
+        EndpointAuth::default()
+    };
+
     let output_file = tokio::fs::OpenOptions::new()
         .create(true)
         .write(true)
@@ -127,31 +316,37 @@ async fn async_main(args: Args) -> Result<(), i32> {
         "mooncake" => {
             let mut dataset = Box::pin(MooncakeDataset::new());
             block_size = 128;
-            dataset.load(
-                dataset_path
-                    .expect("A dataset path must be provided in replay mode!")
-                    .as_str(),
-            );
+            dataset
+                .load(
+                    dataset_path
+                        .expect("A dataset path must be provided in replay mode!")
+                        .as_str(),
+                )
+                .expect("Failed to load dataset");
             dataset
         }
         "burstgpt" => {
             let mut dataset = Box::pin(AzureDataset::new());
             block_size = 16;
-            dataset.load(
-                dataset_path
-                    .expect("A dataset path must be provided in replay mode!")
-                    .as_str(),
-            );
+            dataset
+                .load(
+                    dataset_path
+                        .expect("A dataset path must be provided in replay mode!")
+                        .as_str(),
+                )
+                .expect("Failed to load dataset");
             dataset
         }
         "bailian" => {
             let mut dataset = Box::pin(BailianDataset::new());
             block_size = 16;
-            dataset.load(
-                dataset_path
-                    .expect("A dataset path must be provided in replay mode!")
-                    .as_str(),
-            );
+            dataset
+                .load(
+                    dataset_path
+                        .expect("A dataset path must be provided in replay mode!")
+                        .as_str(),
+                )
+                .expect("Failed to load dataset");
             dataset
         }
         "uniform" => {
@@ -160,23 +355,102 @@ async fn async_main(args: Args) -> Result<(), i32> {
         "azure" => {
             let mut dataset = Box::pin(AzureDataset::new());
             block_size = 16;
-            dataset.load(
-                dataset_path
-                    .expect("A dataset path must be provided in replay mode!")
-                    .as_str(),
-            );
+            dataset
+                .load(
+                    dataset_path
+                        .expect("A dataset path must be provided in replay mode!")
+                        .as_str(),
+                )
+                .expect("Failed to load dataset");
+            dataset
+        }
+        "generic" => {
+            let schema_path = schema_path.expect("--schema-path must be provided for the generic dataset");
+            let schema = TraceSchema::from_json_file(&schema_path)
+                .unwrap_or_else(|err| panic!("Failed to load trace schema from {schema_path}: {err}"));
+            block_size = generic_block_size;
+            let dataset_path = dataset_path.expect("A dataset path must be provided in replay mode!");
+
+            let dataset: Pin<Box<dyn LLMTrace>> = if tolerant_load {
+                let mut generic = GenericTrace::new(schema, block_size);
+                let report = generic
+                    .load_tolerant(&dataset_path)
+                    .expect("Failed to load dataset");
+                tracing::info!(
+                    "loaded {} record(s), skipped {}",
+                    report.loaded,
+                    report.skipped
+                );
+                Box::pin(generic)
+            } else if streaming {
+                let mut generic = GenericTrace::new_streaming(schema, block_size);
+                generic.load(&dataset_path).expect("Failed to load dataset");
+                Box::pin(generic)
+            } else {
+                let mut generic = GenericTrace::new(schema, block_size);
+                generic.load(&dataset_path).expect("Failed to load dataset");
+                Box::pin(generic)
+            };
             dataset
         }
         _ => panic!("Invalid dataset type"),
     };
 
     let (tx, rx) = flume::unbounded();
+    let (dispatch_tx, dispatch_rx) = flume::unbounded();
     let (broadcast_tx, _rx) = broadcast::channel(1);
 
+    let adaptive_config = AdaptiveRateConfig {
+        window: Duration::from_millis(adaptive_window_ms),
+        history_len: adaptive_history_len,
+        slope_threshold: adaptive_slope_threshold,
+        min_rate: adaptive_min_rate,
+        max_rate: adaptive_max_rate,
+        ..AdaptiveRateConfig::default()
+    };
+    let (adaptive_stop_tx, adaptive_stop_rx) = oneshot::channel();
+
+    let retry_policy = RetryPolicy {
+        max_attempts: retries as u32 + 1,
+        ..RetryPolicy::default()
+    };
+    let rate_limiter = rate_limit.map(|rate| {
+        Arc::new(RateLimiter::new(RateLimiterConfig {
+            rate,
+            burst_pct,
+            duration_overhead: Duration::ZERO,
+        }))
+    });
+
     tracing::info!("Client start");
     // TODO: check `spawn_request_loop_with_timestamp` API
-    let requester_handle = match api.to_lowercase().as_str() {
-        "tgi" => {
+    let requester_handle = match (api.to_lowercase().as_str(), adaptive) {
+        ("tgi", true) => {
+            let dataset: Arc<Pin<Box<dyn LLMTrace>>> = Arc::new(dataset);
+            let token_sampler = Arc::new(TokenSampler::new(
+                Tokenizer::from_file(tokenizer).unwrap(),
+                tokenizer_config,
+                num_producer.unwrap_or(1),
+                channel_capacity.unwrap_or(128),
+                block_size,
+            ));
+            let controller =
+                AdaptiveRateController::new(adaptive_config, request_rate.unwrap_or(1.0));
+            spawn_adaptive_request_loop::<TGIApi>(
+                endpoint,
+                dataset,
+                token_sampler,
+                cv,
+                controller,
+                tx,
+                dispatch_tx.clone(),
+                adaptive_stop_rx,
+                RetryPolicy::default(),
+                Duration::from_secs(30),
+                endpoint_auth.clone(),
+            )
+        }
+        ("tgi", false) => {
             let dataset: Arc<Pin<Box<dyn LLMTrace>>> = Arc::new(dataset);
             let token_sampler = Arc::new(TokenSampler::new(
                 Tokenizer::from_file(tokenizer).unwrap(),
@@ -191,16 +465,278 @@ async fn async_main(args: Args) -> Result<(), i32> {
                 token_sampler,
                 scale_factor.unwrap(),
                 tx,
+                dispatch_tx.clone(),
                 broadcast_tx.clone(),
+                retry_policy,
+                Duration::from_secs(30),
+                endpoint_auth.clone(),
+                rate_limiter.clone(),
+            )
+        }
+        ("openai", true) => {
+            let dataset: Arc<Pin<Box<dyn LLMTrace>>> = Arc::new(dataset);
+            let token_sampler = Arc::new(TokenSampler::new(
+                Tokenizer::from_file(tokenizer).unwrap(),
+                tokenizer_config,
+                num_producer.unwrap_or(1),
+                channel_capacity.unwrap_or(128),
+                block_size,
+            ));
+            let controller =
+                AdaptiveRateController::new(adaptive_config, request_rate.unwrap_or(1.0));
+            spawn_adaptive_request_loop::<OpenAICompletionsApi>(
+                endpoint,
+                dataset,
+                token_sampler,
+                cv,
+                controller,
+                tx,
+                dispatch_tx.clone(),
+                adaptive_stop_rx,
+                RetryPolicy::default(),
+                Duration::from_secs(30),
+                endpoint_auth.clone(),
+            )
+        }
+        ("openai", false) => {
+            let dataset: Arc<Pin<Box<dyn LLMTrace>>> = Arc::new(dataset);
+            let token_sampler = Arc::new(TokenSampler::new(
+                Tokenizer::from_file(tokenizer).unwrap(),
+                tokenizer_config,
+                num_producer.unwrap_or(1),
+                channel_capacity.unwrap_or(128),
+                block_size,
+            ));
+            spawn_request_loop_with_timestamp::<OpenAICompletionsApi>(
+                endpoint,
+                dataset,
+                token_sampler,
+                scale_factor.unwrap(),
+                tx,
+                dispatch_tx.clone(),
+                broadcast_tx.clone(),
+                retry_policy,
+                Duration::from_secs(30),
+                endpoint_auth.clone(),
+                rate_limiter.clone(),
+            )
+        }
+        ("tgi-protocol", false) => {
+            let dataset: Arc<Pin<Box<dyn LLMTrace>>> = Arc::new(dataset);
+            let token_sampler = Arc::new(TokenSampler::new(
+                Tokenizer::from_file(&tokenizer).unwrap(),
+                tokenizer_config,
+                num_producer.unwrap_or(1),
+                channel_capacity.unwrap_or(128),
+                block_size,
+            ));
+            let protocol = Arc::new(TgiProtocol::new(Tokenizer::from_file(tokenizer).unwrap()));
+            spawn_protocol_request_loop_with_timestamp(
+                endpoint,
+                dataset,
+                token_sampler,
+                protocol,
+                scale_factor.unwrap(),
+                tx,
+                dispatch_tx.clone(),
+                broadcast_tx.clone(),
+                retry_policy,
+                Duration::from_secs(30),
+                rate_limiter.clone(),
+            )
+        }
+        ("tgi-protocol-batch", false) => {
+            let dataset: Arc<Pin<Box<dyn LLMTrace>>> = Arc::new(dataset);
+            let token_sampler = Arc::new(TokenSampler::new(
+                Tokenizer::from_file(&tokenizer).unwrap(),
+                tokenizer_config,
+                num_producer.unwrap_or(1),
+                channel_capacity.unwrap_or(128),
+                block_size,
+            ));
+            let protocol = Arc::new(TgiProtocol::new(Tokenizer::from_file(tokenizer).unwrap()));
+            spawn_protocol_batch_request_loop_with_timestamp(
+                endpoint,
+                dataset,
+                token_sampler,
+                protocol,
+                scale_factor.unwrap(),
+                max_client_batch_size,
+                tx,
+                dispatch_tx.clone(),
+                broadcast_tx.clone(),
+                retry_policy,
+                Duration::from_secs(30),
+                rate_limiter.clone(),
+            )
+        }
+        ("distserve-protocol", false) => {
+            let dataset: Arc<Pin<Box<dyn LLMTrace>>> = Arc::new(dataset);
+            let token_sampler = Arc::new(TokenSampler::new(
+                Tokenizer::from_file(&tokenizer).unwrap(),
+                tokenizer_config,
+                num_producer.unwrap_or(1),
+                channel_capacity.unwrap_or(128),
+                block_size,
+            ));
+            let protocol = Arc::new(DistserveProtocol::new(
+                Tokenizer::from_file(tokenizer).unwrap(),
+            ));
+            spawn_protocol_request_loop_with_timestamp(
+                endpoint,
+                dataset,
+                token_sampler,
+                protocol,
+                scale_factor.unwrap(),
+                tx,
+                dispatch_tx.clone(),
+                broadcast_tx.clone(),
+                retry_policy,
+                Duration::from_secs(30),
+                rate_limiter.clone(),
+            )
+        }
+        ("vllm-protocol", false) => {
+            let dataset: Arc<Pin<Box<dyn LLMTrace>>> = Arc::new(dataset);
+            let token_sampler = Arc::new(TokenSampler::new(
+                Tokenizer::from_file(&tokenizer).unwrap(),
+                tokenizer_config,
+                num_producer.unwrap_or(1),
+                channel_capacity.unwrap_or(128),
+                block_size,
+            ));
+            let protocol = Arc::new(VllmProtocol::new(Tokenizer::from_file(tokenizer).unwrap()));
+            spawn_protocol_request_loop_with_timestamp(
+                endpoint,
+                dataset,
+                token_sampler,
+                protocol,
+                scale_factor.unwrap(),
+                tx,
+                dispatch_tx.clone(),
+                broadcast_tx.clone(),
+                retry_policy,
+                Duration::from_secs(30),
+                rate_limiter.clone(),
+            )
+        }
+        ("vllm-protocol-batch", false) => {
+            let dataset: Arc<Pin<Box<dyn LLMTrace>>> = Arc::new(dataset);
+            let token_sampler = Arc::new(TokenSampler::new(
+                Tokenizer::from_file(&tokenizer).unwrap(),
+                tokenizer_config,
+                num_producer.unwrap_or(1),
+                channel_capacity.unwrap_or(128),
+                block_size,
+            ));
+            let protocol = Arc::new(VllmProtocol::new(Tokenizer::from_file(tokenizer).unwrap()));
+            spawn_protocol_batch_request_loop_with_timestamp(
+                endpoint,
+                dataset,
+                token_sampler,
+                protocol,
+                scale_factor.unwrap(),
+                max_client_batch_size,
+                tx,
+                dispatch_tx.clone(),
+                broadcast_tx.clone(),
+                retry_policy,
+                Duration::from_secs(30),
+                rate_limiter.clone(),
+            )
+        }
+        ("st-protocol", false) => {
+            let dataset: Arc<Pin<Box<dyn LLMTrace>>> = Arc::new(dataset);
+            let token_sampler = Arc::new(TokenSampler::new(
+                Tokenizer::from_file(&tokenizer).unwrap(),
+                tokenizer_config,
+                num_producer.unwrap_or(1),
+                channel_capacity.unwrap_or(128),
+                block_size,
+            ));
+            let protocol = Arc::new(StProtocol::new(Tokenizer::from_file(tokenizer).unwrap()));
+            spawn_protocol_request_loop_with_timestamp(
+                endpoint,
+                dataset,
+                token_sampler,
+                protocol,
+                scale_factor.unwrap(),
+                tx,
+                dispatch_tx.clone(),
+                broadcast_tx.clone(),
+                retry_policy,
+                Duration::from_secs(30),
+                rate_limiter.clone(),
+            )
+        }
+        ("tgi-dispatch", false) => {
+            let dataset: Arc<Pin<Box<dyn LLMTrace>>> = Arc::new(dataset);
+            let token_sampler = Arc::new(TokenSampler::new(
+                Tokenizer::from_file(tokenizer).unwrap(),
+                tokenizer_config,
+                num_producer.unwrap_or(1),
+                channel_capacity.unwrap_or(128),
+                block_size,
+            ));
+            let dispatcher = Arc::new(Dispatcher::new(dispatch_workers, dispatch_queue_capacity));
+            spawn_dispatcher_request_loop_with_timestamp::<TGIApi>(
+                endpoint,
+                dataset,
+                token_sampler,
+                scale_factor.unwrap(),
+                dispatcher,
+                tx,
+                dispatch_tx.clone(),
+                broadcast_tx.clone(),
+                retry_policy,
+                Duration::from_secs(30),
+                endpoint_auth.clone(),
+                rate_limiter.clone(),
+            )
+        }
+        ("openai-dispatch", false) => {
+            let dataset: Arc<Pin<Box<dyn LLMTrace>>> = Arc::new(dataset);
+            let token_sampler = Arc::new(TokenSampler::new(
+                Tokenizer::from_file(tokenizer).unwrap(),
+                tokenizer_config,
+                num_producer.unwrap_or(1),
+                channel_capacity.unwrap_or(128),
+                block_size,
+            ));
+            let dispatcher = Arc::new(Dispatcher::new(dispatch_workers, dispatch_queue_capacity));
+            spawn_dispatcher_request_loop_with_timestamp::<OpenAICompletionsApi>(
+                endpoint,
+                dataset,
+                token_sampler,
+                scale_factor.unwrap(),
+                dispatcher,
+                tx,
+                dispatch_tx.clone(),
+                broadcast_tx.clone(),
+                retry_policy,
+                Duration::from_secs(30),
+                endpoint_auth.clone(),
+                rate_limiter.clone(),
             )
         }
         _ => unimplemented!("Unsupported protocol type"),
     };
-    let reporter_handle = spawn(report_loop(output_file, rx));
+    let reporter_handle = if let Some(admin_addr) = admin_addr {
+        spawn(report_loop_with_admin(output_file, rx, dispatch_rx, admin_addr))
+    } else if tui {
+        spawn(report_loop_with_tui(output_file, rx, dispatch_rx))
+    } else {
+        drop(dispatch_rx);
+        spawn(report_loop(output_file, rx))
+    };
 
     // start test!
     tokio::time::sleep(tokio::time::Duration::from_secs(time_in_secs)).await;
-    broadcast_tx.send(()).unwrap(); // terminate test
+    if adaptive {
+        let _ = adaptive_stop_tx.send(());
+    } else {
+        broadcast_tx.send(()).unwrap(); // terminate test
+    }
 
     let returnval = requester_handle.await.unwrap();
     reporter_handle.await.unwrap();
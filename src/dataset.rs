@@ -1,6 +1,5 @@
 use std::{
-    cell::UnsafeCell,
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs::File,
     io::{BufRead, BufReader},
     sync::atomic::{AtomicUsize, Ordering},
@@ -9,11 +8,13 @@ use std::{
 use crate::{
     metrics::{self, SystemMetrics},
     token_sampler::TokenSampler,
+    trace_schema::TraceSchema,
     SpinRwLock,
 };
 use chrono::NaiveDateTime;
+use memmap2::Mmap;
 use serde::{Deserialize, Serialize};
-use tracing::{instrument, Level}; 
+use tracing::{instrument, Level};
 
 /// jsonl of Bailian
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,8 +80,56 @@ impl Iterator for DataIter {
 unsafe impl Send for DataIter {}
 unsafe impl Sync for DataIter {}
 
+/// Why [`LLMTrace::load`] failed to read a trace file, distinguishing failures a caller might
+/// want to handle differently: the file itself is unreadable, a record didn't parse at all, or a
+/// column's value didn't match its expected format.
+#[derive(Debug)]
+pub enum TraceLoadError {
+    /// The file couldn't be opened or read.
+    Io(std::io::Error),
+    /// A record's raw text didn't deserialize into the expected row shape.
+    Deserialize {
+        line: usize,
+        raw: String,
+        reason: String,
+    },
+    /// A column's value didn't convert to the type/format its schema field expects.
+    Conversion {
+        column: String,
+        expected_format: String,
+        raw: String,
+    },
+}
+
+impl std::fmt::Display for TraceLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceLoadError::Io(err) => write!(f, "I/O error reading trace file: {err}"),
+            TraceLoadError::Deserialize { line, raw, reason } => {
+                write!(f, "line {line}: failed to parse record {raw:?}: {reason}")
+            }
+            TraceLoadError::Conversion {
+                column,
+                expected_format,
+                raw,
+            } => write!(
+                f,
+                "column '{column}': value {raw:?} does not match expected format {expected_format}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TraceLoadError {}
+
+impl From<std::io::Error> for TraceLoadError {
+    fn from(err: std::io::Error) -> Self {
+        TraceLoadError::Io(err)
+    }
+}
+
 pub trait LLMTrace: Send + Sync {
-    fn load(&mut self, path: &str);
+    fn load(&mut self, path: &str) -> Result<(), TraceLoadError>;
     fn timestamp(&self, index: usize) -> u64;
     fn inflate(&self, index: usize, ts: &TokenSampler) -> (String, u64, u64, SystemMetrics);
     fn iter(&self) -> DataIter;
@@ -92,31 +141,33 @@ pub trait LLMTrace: Send + Sync {
 //
 pub struct BailianDataset {
     items: Vec<BailianDataItem>,
-    user_prompts: UnsafeCell<HashMap<u64, String>>,
-    rwlock: SpinRwLock,
+    user_prompts: SpinRwLock<HashMap<u64, String>>,
 }
 
 impl BailianDataset {
     pub fn new() -> Self {
         Self {
             items: Vec::new(),
-            user_prompts: UnsafeCell::new(HashMap::new()),
-            rwlock: SpinRwLock::new(),
+            user_prompts: SpinRwLock::new(HashMap::new()),
         }
     }
 }
 
-unsafe impl Send for BailianDataset {}
-unsafe impl Sync for BailianDataset {}
-
 impl LLMTrace for BailianDataset {
-    fn load(&mut self, path: &str) {
-        let file = File::open(path).unwrap();
+    fn load(&mut self, path: &str) -> Result<(), TraceLoadError> {
+        let file = File::open(path)?;
 
-        for line in BufReader::new(file).lines() {
-            let item: BailianDataItem = serde_json::from_str(line.unwrap().as_str()).unwrap();
+        for (line_no, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            let item: BailianDataItem =
+                serde_json::from_str(&line).map_err(|err| TraceLoadError::Deserialize {
+                    line: line_no + 1,
+                    raw: line.clone(),
+                    reason: err.to_string(),
+                })?;
             self.items.push(item);
         }
+        Ok(())
     }
 
     fn iter(&self) -> DataIter {
@@ -139,59 +190,52 @@ impl LLMTrace for BailianDataset {
     fn inflate(&self, index: usize, ts: &TokenSampler) -> (String, u64, u64, SystemMetrics) {
         // NOTE: the last block hash may be hashed onto a partially filled block
         const BLOCK_SIZE: usize = 16;
-        unsafe {
-            let data_item = self.items.get(index).unwrap();
-            let last_block_len =
-                (*data_item).input_length as usize - ((*data_item).hash_ids.len() - 1) * BLOCK_SIZE;
-            debug_assert!(last_block_len <= BLOCK_SIZE);
-
-            let mut prompt = String::new();
-            for &hash_id in (*data_item)
-                .hash_ids
-                .iter()
-                .take((*data_item).hash_ids.len() - 1)
-            {
-                // loop invariant: rwlock is free
-                self.rwlock.read_lock();
-                if let Some(s) = (&*self.user_prompts.get()).get(&hash_id) {
-                    prompt.push_str(&s);
-                    self.rwlock.read_unlock();
+        let data_item = self.items.get(index).unwrap();
+        let last_block_len =
+            data_item.input_length as usize - (data_item.hash_ids.len() - 1) * BLOCK_SIZE;
+        debug_assert!(last_block_len <= BLOCK_SIZE);
+
+        let mut prompt = String::new();
+        for &hash_id in data_item.hash_ids.iter().take(data_item.hash_ids.len() - 1) {
+            // Read the lock in its own statement so the guard is dropped before a miss falls
+            // through to `write_lock()` below -- holding it across both would deadlock.
+            let cached = self.user_prompts.read_lock().get(&hash_id).cloned();
+            if let Some(s) = cached {
+                prompt.push_str(&s);
+            } else {
+                let s = ts.gen_string(BLOCK_SIZE);
+                let mut user_prompts = self.user_prompts.write_lock();
+                if let Some(s0) = user_prompts.get(&hash_id) {
+                    prompt.push_str(s0);
                 } else {
-                    self.rwlock.read_unlock();
-                    let s = ts.gen_string(BLOCK_SIZE);
-                    self.rwlock.write_lock();
-                    if let Some(s0) = (*self.user_prompts.get()).get(&hash_id) {
-                        prompt.push_str(&s0);
-                    } else {
-                        prompt.push_str(&s);
-                        (&mut *self.user_prompts.get()).insert(hash_id, s);
-                    }
-                    self.rwlock.write_unlock();
+                    prompt.push_str(&s);
+                    user_prompts.insert(hash_id, s);
                 }
             }
-
-            let last_block_prompt = ts.gen_string(last_block_len);
-            prompt.push_str(&last_block_prompt);
-            self.rwlock.write_lock();
-            (&mut *self.user_prompts.get())
-                .insert(*(*data_item).hash_ids.last().unwrap(), last_block_prompt);
-            self.rwlock.write_unlock();
-
-            (
-                prompt,
-                (*data_item).input_length,
-                (*data_item).output_length,
-                SystemMetrics {
-                    generate_time: None,
-                    get_prompt_time: None,
-                    sample_time: None,
-                    inflate_time: None,
-                    send_gap: None,
-                    prev_sample_time: None,
-                    post_sample_time: None,
-                },
-            )
         }
+
+        let last_block_prompt = ts.gen_string(last_block_len);
+        prompt.push_str(&last_block_prompt);
+        self.user_prompts
+            .write_lock()
+            .insert(*data_item.hash_ids.last().unwrap(), last_block_prompt);
+
+        (
+            prompt,
+            data_item.input_length,
+            data_item.output_length,
+            SystemMetrics {
+                generate_time: None,
+                get_prompt_time: None,
+                sample_time: None,
+                inflate_time: None,
+                send_gap: None,
+                prev_sample_time: None,
+                post_sample_time: None,
+                attempt_count: None,
+                attempt_latencies_ms: None,
+            },
+        )
     }
 }
 
@@ -200,30 +244,32 @@ impl LLMTrace for BailianDataset {
 //
 pub struct MooncakeDataset {
     items: Vec<MooncakeDataItem>,
-    user_prompts: UnsafeCell<HashMap<u64, String>>,
-    rwlock: SpinRwLock,
+    user_prompts: SpinRwLock<HashMap<u64, String>>,
 }
 
-unsafe impl Send for MooncakeDataset {}
-unsafe impl Sync for MooncakeDataset {}
-
 impl MooncakeDataset {
     pub fn new() -> Self {
         Self {
             items: Vec::new(),
-            user_prompts: UnsafeCell::new(HashMap::new()),
-            rwlock: SpinRwLock::new(),
+            user_prompts: SpinRwLock::new(HashMap::new()),
         }
     }
 }
 
 impl LLMTrace for MooncakeDataset {
-    fn load(&mut self, path: &str) {
-        let file = File::open(path).unwrap();
-        for line in BufReader::new(file).lines() {
-            let item: MooncakeDataItem = serde_json::from_str(line.unwrap().as_str()).unwrap();
+    fn load(&mut self, path: &str) -> Result<(), TraceLoadError> {
+        let file = File::open(path)?;
+        for (line_no, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            let item: MooncakeDataItem =
+                serde_json::from_str(&line).map_err(|err| TraceLoadError::Deserialize {
+                    line: line_no + 1,
+                    raw: line.clone(),
+                    reason: err.to_string(),
+                })?;
             self.items.push(item);
         }
+        Ok(())
     }
 
     fn iter(&self) -> DataIter {
@@ -246,60 +292,52 @@ impl LLMTrace for MooncakeDataset {
     fn inflate(&self, index: usize, ts: &TokenSampler) -> (String, u64, u64, SystemMetrics) {
         // NOTE: the last block hash may be hashed onto a partially filled block
         const BLOCK_SIZE: usize = 512;
-        unsafe {
-            let data_item = self.items.get(index).unwrap();
-            let last_block_len =
-                (*data_item).input_length as usize - ((*data_item).hash_ids.len() - 1) * BLOCK_SIZE;
-            debug_assert!(last_block_len <= BLOCK_SIZE);
-
-            let mut prompt = String::new();
-            for &hash_id in (*data_item)
-                .hash_ids
-                .iter()
-                .take((*data_item).hash_ids.len() - 1)
-            {
-                // loop invariant: rwlock is free
-                self.rwlock.read_lock();
-                if let Some(s) = (&*self.user_prompts.get()).get(&hash_id) {
-                    prompt.push_str(&s);
-                    self.rwlock.read_unlock();
+        let data_item = self.items.get(index).unwrap();
+        let last_block_len =
+            data_item.input_length as usize - (data_item.hash_ids.len() - 1) * BLOCK_SIZE;
+        debug_assert!(last_block_len <= BLOCK_SIZE);
+
+        let mut prompt = String::new();
+        for &hash_id in data_item.hash_ids.iter().take(data_item.hash_ids.len() - 1) {
+            // Read the lock in its own statement so the guard is dropped before a miss falls
+            // through to `write_lock()` below -- holding it across both would deadlock.
+            let cached = self.user_prompts.read_lock().get(&hash_id).cloned();
+            if let Some(s) = cached {
+                prompt.push_str(&s);
+            } else {
+                let s = ts.gen_string(BLOCK_SIZE);
+                let mut user_prompts = self.user_prompts.write_lock();
+                if let Some(s0) = user_prompts.get(&hash_id) {
+                    prompt.push_str(s0);
                 } else {
-                    self.rwlock.read_unlock();
-                    let s = ts.gen_string(BLOCK_SIZE);
-                    self.rwlock.write_lock();
-                    if let Some(s0) = (*self.user_prompts.get()).get(&hash_id) {
-                        prompt.push_str(&s0);
-                    } else {
-                        prompt.push_str(&s);
-                        (&mut *self.user_prompts.get()).insert(hash_id, s);
-                    }
-                    self.rwlock.write_unlock();
+                    prompt.push_str(&s);
+                    user_prompts.insert(hash_id, s);
                 }
             }
-            // postcond: rwlock is free
-
-            let last_block_prompt = ts.gen_string(last_block_len);
-            prompt.push_str(&last_block_prompt);
-            self.rwlock.write_lock();
-            (&mut *self.user_prompts.get())
-                .insert(*(*data_item).hash_ids.last().unwrap(), last_block_prompt);
-            self.rwlock.write_unlock();
-
-            (
-                prompt,
-                (*data_item).input_length,
-                (*data_item).output_length,
-                SystemMetrics {
-                    generate_time: None,
-                    get_prompt_time: None,
-                    sample_time: None,
-                    inflate_time: None,
-                    send_gap: None,
-                    prev_sample_time: None,
-                    post_sample_time: None,
-                },
-            )
         }
+
+        let last_block_prompt = ts.gen_string(last_block_len);
+        prompt.push_str(&last_block_prompt);
+        self.user_prompts
+            .write_lock()
+            .insert(*data_item.hash_ids.last().unwrap(), last_block_prompt);
+
+        (
+            prompt,
+            data_item.input_length,
+            data_item.output_length,
+            SystemMetrics {
+                generate_time: None,
+                get_prompt_time: None,
+                sample_time: None,
+                inflate_time: None,
+                send_gap: None,
+                prev_sample_time: None,
+                post_sample_time: None,
+                attempt_count: None,
+                attempt_latencies_ms: None,
+            },
+        )
     }
 }
 
@@ -309,31 +347,36 @@ impl LLMTrace for MooncakeDataset {
 pub struct AzureDataset {
     start_time: u64,
     items: Vec<AzureDataItem>,
-    user_prompts: UnsafeCell<Vec<String>>, // each string represents 16 tokens
-    rwlock: SpinRwLock,
-    // user_prompts_map: UnsafeCell<HashMap<usize, String>>,
+    user_prompts: SpinRwLock<Vec<String>>, // each string represents 16 tokens
 }
 
-unsafe impl Send for AzureDataset {}
-unsafe impl Sync for AzureDataset {}
-
 impl AzureDataset {
     pub fn new() -> Self {
         Self {
             items: Vec::new(),
-            user_prompts: UnsafeCell::new(Vec::with_capacity(1024)),
-            rwlock: SpinRwLock::new(),
+            user_prompts: SpinRwLock::new(Vec::with_capacity(1024)),
             start_time: 0,
-            // user_prompts_map: UnsafeCell::new(HashMap::new()),
         }
     }
 }
 
 impl LLMTrace for AzureDataset {
-    fn load(&mut self, path: &str) {
-        let mut rdr = csv::Reader::from_path(path).unwrap();
-        for result in rdr.deserialize() {
-            let mut record: AzureDataItem = result.unwrap();
+    fn load(&mut self, path: &str) -> Result<(), TraceLoadError> {
+        let mut rdr = csv::Reader::from_path(path)
+            .map_err(|err| TraceLoadError::Io(std::io::Error::other(err.to_string())))?;
+        let headers = rdr
+            .headers()
+            .map_err(|err| TraceLoadError::Io(std::io::Error::other(err.to_string())))?
+            .clone();
+        for (line_no, result) in rdr.records().enumerate() {
+            let row = result.map_err(|err| TraceLoadError::Io(std::io::Error::other(err.to_string())))?;
+            let mut record: AzureDataItem =
+                row.deserialize(Some(&headers))
+                    .map_err(|err| TraceLoadError::Deserialize {
+                        line: line_no + 1,
+                        raw: row.iter().collect::<Vec<_>>().join(","),
+                        reason: err.to_string(),
+                    })?;
             if self.start_time == 0 {
                 self.start_time = record.naive_timestamp.and_utc().timestamp_millis() as u64;
             }
@@ -341,6 +384,7 @@ impl LLMTrace for AzureDataset {
                 record.naive_timestamp.and_utc().timestamp_millis() as u64 - self.start_time;
             self.items.push(record);
         }
+        Ok(())
     }
 
     fn iter(&self) -> DataIter {
@@ -370,93 +414,596 @@ impl LLMTrace for AzureDataset {
     }
 
     fn inflate(&self, index: usize, ts: &TokenSampler) -> (String, u64, u64, SystemMetrics) {
-        unsafe {
-            let inflate_start_time = std::time::Instant::now();
-            let mut metrics = SystemMetrics {
-                generate_time: None,
-                get_prompt_time: None,
-                sample_time: None,
-                inflate_time: None,
-                send_gap: None,
-                prev_sample_time: None,
-                post_sample_time: None,
-            };
-            // tracing::info!("Inflating index {}", index);
-            let AzureDataItem {
-                timestamp: _,
-                context_tokens,
-                generated_tokens,
-                naive_timestamp: _,
-            } = self.items.get(index).unwrap().clone();
-
-            let last_block_len = (context_tokens % 16) as usize;
-            let num_blocks = (context_tokens as usize - last_block_len) / 16;
-
-            let mut prompt = String::new();
-            self.rwlock.read_lock();
-            let n = (&*self.user_prompts.get()).len();
-
-            let read_lock_time = inflate_start_time.elapsed().as_millis();
-            metrics.prev_sample_time = Some(read_lock_time as u64);
-            if n >= num_blocks {
-                let get_prompt_start_time = std::time::Instant::now();
-                for s in &(&(*self.user_prompts.get()))[0..num_blocks] {
-                    prompt.push_str(s);
-                }
-                // tracing::info!("no need to generate new blocks, current blocks = {}", n);
-                self.rwlock.read_unlock();
-                let end_time = get_prompt_start_time.elapsed().as_millis();
-                metrics.get_prompt_time = Some(end_time as u64);
+        let inflate_start_time = std::time::Instant::now();
+        let mut metrics = SystemMetrics {
+            generate_time: None,
+            get_prompt_time: None,
+            sample_time: None,
+            inflate_time: None,
+            send_gap: None,
+            prev_sample_time: None,
+            post_sample_time: None,
+            attempt_count: None,
+            attempt_latencies_ms: None,
+        };
+        // tracing::info!("Inflating index {}", index);
+        let AzureDataItem {
+            timestamp: _,
+            context_tokens,
+            generated_tokens,
+            naive_timestamp: _,
+        } = self.items.get(index).unwrap().clone();
+
+        let last_block_len = (context_tokens % 16) as usize;
+        let num_blocks = (context_tokens as usize - last_block_len) / 16;
+
+        let mut prompt = String::new();
+        let user_prompts = self.user_prompts.read_lock();
+        let n = user_prompts.len();
+
+        let read_lock_time = inflate_start_time.elapsed().as_millis();
+        metrics.prev_sample_time = Some(read_lock_time as u64);
+        if n >= num_blocks {
+            let get_prompt_start_time = std::time::Instant::now();
+            for s in &user_prompts[0..num_blocks] {
+                prompt.push_str(s);
+            }
+            // tracing::info!("no need to generate new blocks, current blocks = {}", n);
+            drop(user_prompts);
+            let end_time = get_prompt_start_time.elapsed().as_millis();
+            metrics.get_prompt_time = Some(end_time as u64);
+        } else {
+            let generate_start_time = std::time::Instant::now();
+            for s in &user_prompts[0..n] {
+                prompt.push_str(s);
+            }
+            // tracing::info!(
+            //     "need to generate {} new blocks, current blocks = {}",
+            //     num_blocks - n,
+            //     n
+            // );
+            drop(user_prompts);
+            let new_prompts: Vec<String> = (n..num_blocks).map(|_| ts.gen_string(16)).collect();
+            for s in new_prompts.iter() {
+                prompt.push_str(s);
+            }
+
+            // tracing::info!("waiting for write lock, index = {}", index);
+            self.user_prompts.write_lock().extend(new_prompts);
+            let end_time = generate_start_time.elapsed().as_millis();
+            metrics.generate_time = Some(end_time as u64);
+        }
+        // postcond: self.user_prompts is unlocked
+        // tracing::info!("generating last block of length {}", last_block_len);
+        let post_sample_time = std::time::Instant::now();
+        if last_block_len != 0 {
+            let last_block_prompt = ts.gen_string(last_block_len);
+            prompt.push_str(&last_block_prompt);
+        }
+
+        let end_time = inflate_start_time.elapsed().as_millis();
+        metrics.inflate_time = Some(end_time as u64);
+        metrics.post_sample_time = Some(post_sample_time.elapsed().as_millis() as u64);
+        (prompt, context_tokens, generated_tokens, metrics)
+    }
+}
+//
+// ============== GenericTrace ==============
+//
+
+/// One parsed row of a [`GenericTrace`].
+struct GenericTraceItem {
+    timestamp: u64,
+    input_length: u64,
+    output_length: u64,
+    /// Per-`block_size`-chunk content hashes, used to dedupe repeated prompt prefixes the same
+    /// way [`BailianDataset`]/[`MooncakeDataset`] do. Rows without a `hash_ids` column (or whose
+    /// schema omits it) fall back to generating the whole prompt fresh every time.
+    hash_ids: Option<Vec<u64>>,
+}
+
+/// Byte range of one unparsed record in a memory-mapped trace file, plus the one field
+/// [`GenericTrace::timestamp`]/[`GenericTrace::rps`] need without deserializing the whole row.
+struct RecordIndexEntry {
+    offset: usize,
+    len: usize,
+    timestamp: u64,
+}
+
+/// How to split a [`RecordIndexEntry`]'s raw bytes back into named columns.
+enum StreamingFormat {
+    Jsonl,
+    Csv { headers: Vec<String> },
+}
+
+/// Backing storage built by [`GenericTrace::load`] for a trace opened with
+/// [`GenericTrace::new_streaming`]: the whole file stays memory-mapped and only a byte-offset
+/// index is held in memory, so a many-GB trace can be replayed with bounded resident memory.
+struct StreamingTrace {
+    mmap: Mmap,
+    format: StreamingFormat,
+    index: Vec<RecordIndexEntry>,
+}
+
+/// Either fully materialized rows ([`GenericTrace::new`]) or a memory-mapped file plus a
+/// lightweight offset index ([`GenericTrace::new_streaming`]).
+enum TraceStorage {
+    Eager(Vec<GenericTraceItem>),
+    Streaming(StreamingTrace),
+}
+
+/// Loads any CSV or JSONL trace file according to a [`TraceSchema`] instead of a hand-written
+/// struct with hardcoded field names, so a new trace format can be onboarded without a code
+/// change. Format is auto-detected from the file extension (`.csv` vs. anything else = JSONL).
+///
+/// Subsumes [`BailianDataset`], [`MooncakeDataset`], and [`AzureDataset`]: each of their formats
+/// is just a particular [`TraceSchema`] (a fixed `block_size`, and a `hash_ids` column for the
+/// first two but not the third).
+pub struct GenericTrace {
+    schema: TraceSchema,
+    block_size: usize,
+    start_time: u64,
+    streaming: bool,
+    storage: TraceStorage,
+    user_prompts: SpinRwLock<HashMap<u64, String>>,
+}
+
+impl GenericTrace {
+    /// Eagerly reads every record into memory at `load` time, same as the hand-written datasets.
+    pub fn new(schema: TraceSchema, block_size: usize) -> Self {
+        Self {
+            schema,
+            block_size,
+            start_time: 0,
+            streaming: false,
+            storage: TraceStorage::Eager(Vec::new()),
+            user_prompts: SpinRwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Memory-maps the trace file at `load` time instead of reading it into a `Vec`: only a
+    /// byte-offset index is kept resident, and each record is deserialized on demand by
+    /// [`LLMTrace::inflate`]. Use this for traces too large to fit comfortably in memory.
+    pub fn new_streaming(schema: TraceSchema, block_size: usize) -> Self {
+        Self {
+            streaming: true,
+            ..Self::new(schema, block_size)
+        }
+    }
+
+    /// Re-render the prompt for a parsed row, deduping repeated block-hash prefixes against
+    /// `self.user_prompts` the same way regardless of whether the row came from `self.storage`'s
+    /// eager `Vec` or its streaming mmap index.
+    fn generate_prompt(
+        &self,
+        input_length: u64,
+        output_length: u64,
+        hash_ids: Option<&Vec<u64>>,
+        ts: &TokenSampler,
+    ) -> (String, u64, u64, SystemMetrics) {
+        let metrics = SystemMetrics {
+            generate_time: None,
+            get_prompt_time: None,
+            sample_time: None,
+            inflate_time: None,
+            send_gap: None,
+            prev_sample_time: None,
+            post_sample_time: None,
+            attempt_count: None,
+            attempt_latencies_ms: None,
+        };
+        let Some(hash_ids) = hash_ids else {
+            // No block hashes to dedupe against: just generate the whole prompt fresh.
+            return (
+                ts.gen_string(input_length as usize),
+                input_length,
+                output_length,
+                metrics,
+            );
+        };
+
+        let last_block_len = input_length as usize - (hash_ids.len() - 1) * self.block_size;
+        debug_assert!(last_block_len <= self.block_size);
+
+        let mut prompt = String::new();
+        for &hash_id in hash_ids.iter().take(hash_ids.len() - 1) {
+            // Read the lock in its own statement so the guard is dropped before a miss falls
+            // through to `write_lock()` below -- holding it across both would deadlock.
+            let cached = self.user_prompts.read_lock().get(&hash_id).cloned();
+            if let Some(s) = cached {
+                prompt.push_str(&s);
             } else {
-                let generate_start_time = std::time::Instant::now();
-                for s in &(&(*self.user_prompts.get()))[0..n] {
-                    prompt.push_str(s);
+                let s = ts.gen_string(self.block_size);
+                let mut user_prompts = self.user_prompts.write_lock();
+                if let Some(s0) = user_prompts.get(&hash_id) {
+                    prompt.push_str(s0);
+                } else {
+                    prompt.push_str(&s);
+                    user_prompts.insert(hash_id, s);
                 }
-                // tracing::info!(
-                //     "need to generate {} new blocks, current blocks = {}",
-                //     num_blocks - n,
-                //     n
-                // );
-                self.rwlock.read_unlock();
-                let new_prompts: Vec<String> = (n..num_blocks).map(|_| ts.gen_string(16)).collect();
-                for s in new_prompts.iter() {
-                    prompt.push_str(s);
+            }
+        }
+
+        let last_block_prompt = ts.gen_string(last_block_len);
+        prompt.push_str(&last_block_prompt);
+        self.user_prompts
+            .write_lock()
+            .insert(*hash_ids.last().unwrap(), last_block_prompt);
+
+        (prompt, input_length, output_length, metrics)
+    }
+
+    /// Parse the row at `index` of a [`StreamingTrace`] back into named columns, re-slicing the
+    /// still-mapped file rather than anything copied out at `load` time.
+    fn streaming_row(&self, streaming: &StreamingTrace, index: usize) -> HashMap<String, String> {
+        let entry = &streaming.index[index];
+        let line = std::str::from_utf8(&streaming.mmap[entry.offset..entry.offset + entry.len])
+            .unwrap_or_default();
+        let line = line.trim_end_matches('\r');
+        match &streaming.format {
+            StreamingFormat::Jsonl => parse_jsonl_line(line),
+            StreamingFormat::Csv { headers } => parse_csv_line(line, headers),
+        }
+    }
+}
+
+/// Read a JSONL file into one `HashMap<column, raw value>` per line. String fields pass through
+/// unquoted; every other JSON type is kept as its JSON text (so `Conversion::Bytes` on a
+/// `hash_ids` array yields `"[1,2,3]"`, re-parseable with `serde_json::from_str`).
+fn load_jsonl_rows(path: &str) -> Result<Vec<HashMap<String, String>>, TraceLoadError> {
+    let file = File::open(path)?;
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .map(|(line_no, line)| {
+            let line = line?;
+            let value: serde_json::Value =
+                serde_json::from_str(&line).map_err(|err| TraceLoadError::Deserialize {
+                    line: line_no + 1,
+                    raw: line.clone(),
+                    reason: err.to_string(),
+                })?;
+            Ok(value
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(column, value)| {
+                    let raw = match value {
+                        serde_json::Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    (column.clone(), raw)
+                })
+                .collect())
+        })
+        .collect()
+}
+
+/// Read a CSV file into one `HashMap<header, cell>` per record.
+fn load_csv_rows(path: &str) -> Result<Vec<HashMap<String, String>>, TraceLoadError> {
+    let mut rdr = csv::Reader::from_path(path)
+        .map_err(|err| TraceLoadError::Io(std::io::Error::other(err.to_string())))?;
+    let headers = rdr
+        .headers()
+        .map_err(|err| TraceLoadError::Io(std::io::Error::other(err.to_string())))?
+        .clone();
+    rdr.records()
+        .map(|record| {
+            let record =
+                record.map_err(|err| TraceLoadError::Io(std::io::Error::other(err.to_string())))?;
+            Ok(headers
+                .iter()
+                .zip(record.iter())
+                .map(|(header, cell)| (header.to_string(), cell.to_string()))
+                .collect())
+        })
+        .collect()
+}
+
+/// Parse a single JSONL line into a `HashMap<column, raw value>`, the line-at-a-time counterpart
+/// to [`load_jsonl_rows`] used by [`GenericTrace`]'s streaming, memory-mapped load path.
+fn parse_jsonl_line(line: &str) -> HashMap<String, String> {
+    let value: serde_json::Value = serde_json::from_str(line).unwrap();
+    value
+        .as_object()
+        .unwrap()
+        .iter()
+        .map(|(column, value)| {
+            let raw = match value {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            (column.clone(), raw)
+        })
+        .collect()
+}
+
+/// Parse a single CSV record line against `headers` into a `HashMap<header, cell>`, the
+/// line-at-a-time counterpart to [`load_csv_rows`] used by [`GenericTrace`]'s streaming,
+/// memory-mapped load path.
+fn parse_csv_line(line: &str, headers: &[String]) -> HashMap<String, String> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(line.as_bytes());
+    let record = rdr.records().next().unwrap().unwrap();
+    headers
+        .iter()
+        .zip(record.iter())
+        .map(|(header, cell)| (header.clone(), cell.to_string()))
+        .collect()
+}
+
+impl LLMTrace for GenericTrace {
+    fn load(&mut self, path: &str) -> Result<(), TraceLoadError> {
+        if self.streaming {
+            self.load_streaming(path)
+        } else {
+            self.load_eager(path)
+        }
+    }
+
+    fn iter(&self) -> DataIter {
+        let size = match &self.storage {
+            TraceStorage::Eager(items) => items.len(),
+            TraceStorage::Streaming(streaming) => streaming.index.len(),
+        };
+        DataIter {
+            size,
+            index: AtomicUsize::new(0),
+        }
+    }
+
+    fn rps(&self) -> f64 {
+        let (first, last, count) = match &self.storage {
+            TraceStorage::Eager(items) => (
+                items.first().unwrap().timestamp,
+                items.last().unwrap().timestamp,
+                items.len(),
+            ),
+            TraceStorage::Streaming(streaming) => (
+                streaming.index.first().unwrap().timestamp,
+                streaming.index.last().unwrap().timestamp,
+                streaming.index.len(),
+            ),
+        };
+        let seconds = (last - first) as f64 / 1000.0;
+        if seconds > 0.0 {
+            count as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    fn timestamp(&self, index: usize) -> u64 {
+        match &self.storage {
+            TraceStorage::Eager(items) => items[index].timestamp,
+            TraceStorage::Streaming(streaming) => streaming.index[index].timestamp,
+        }
+    }
+
+    fn inflate(&self, index: usize, ts: &TokenSampler) -> (String, u64, u64, SystemMetrics) {
+        match &self.storage {
+            TraceStorage::Eager(items) => {
+                let item = &items[index];
+                self.generate_prompt(
+                    item.input_length,
+                    item.output_length,
+                    item.hash_ids.as_ref(),
+                    ts,
+                )
+            }
+            TraceStorage::Streaming(streaming) => {
+                let row = self.streaming_row(streaming, index);
+                let mut parsed = BTreeMap::new();
+                self.schema.apply(&row, &mut parsed);
+                let input_length = parsed
+                    .get("input_length")
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .unwrap_or(0);
+                let output_length = parsed
+                    .get("output_length")
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .unwrap_or(0);
+                let hash_ids = parsed
+                    .get("hash_ids")
+                    .and_then(|v| serde_json::from_str::<Vec<u64>>(v).ok());
+                self.generate_prompt(input_length, output_length, hash_ids.as_ref(), ts)
+            }
+        }
+    }
+}
+
+impl GenericTrace {
+    fn load_eager(&mut self, path: &str) -> Result<(), TraceLoadError> {
+        let rows = if path.ends_with(".csv") {
+            load_csv_rows(path)?
+        } else {
+            load_jsonl_rows(path)?
+        };
+
+        let mut items = Vec::new();
+        for row in rows {
+            let mut parsed = BTreeMap::new();
+            self.schema.apply(&row, &mut parsed);
+
+            if let Some(errors) = parsed.remove("errors") {
+                return Err(TraceLoadError::Conversion {
+                    column: "schema".to_string(),
+                    expected_format: "fields declared in the trace schema".to_string(),
+                    raw: errors,
+                });
+            }
+
+            let (Some(timestamp), Some(input_length), Some(output_length)) = (
+                parsed.get("timestamp").and_then(|v| v.parse::<i64>().ok()),
+                parsed.get("input_length").and_then(|v| v.parse::<u64>().ok()),
+                parsed.get("output_length").and_then(|v| v.parse::<u64>().ok()),
+            ) else {
+                continue;
+            };
+            let hash_ids = parsed
+                .get("hash_ids")
+                .and_then(|v| serde_json::from_str::<Vec<u64>>(v).ok());
+
+            // Same "first row sets the epoch" normalization `AzureDataset::load` uses.
+            if self.start_time == 0 {
+                self.start_time = timestamp as u64;
+            }
+            items.push(GenericTraceItem {
+                timestamp: (timestamp as u64).saturating_sub(self.start_time),
+                input_length,
+                output_length,
+                hash_ids,
+            });
+        }
+        self.storage = TraceStorage::Eager(items);
+        Ok(())
+    }
+
+    /// Memory-map `path` and build only a byte-offset index over its records (plus each one's
+    /// normalized timestamp), instead of keeping every deserialized row in memory. Each row is
+    /// still parsed once here to find its timestamp, then dropped. Record content is read
+    /// from the mmap again, lazily, by [`LLMTrace::inflate`].
+    fn load_streaming(&mut self, path: &str) -> Result<(), TraceLoadError> {
+        let file = File::open(path)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        let is_csv = path.ends_with(".csv");
+
+        let mut offset = 0usize;
+        let mut headers = Vec::new();
+        if is_csv {
+            let header_end = mmap[offset..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map(|p| offset + p)
+                .unwrap_or(mmap.len());
+            let header_line = String::from_utf8_lossy(&mmap[offset..header_end]);
+            headers = header_line
+                .trim_end_matches('\r')
+                .split(',')
+                .map(|s| s.to_string())
+                .collect();
+            offset = (header_end + 1).min(mmap.len());
+        }
+
+        let mut index = Vec::new();
+        while offset < mmap.len() {
+            let end = mmap[offset..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map(|p| offset + p)
+                .unwrap_or(mmap.len());
+            if end > offset {
+                let line = String::from_utf8_lossy(&mmap[offset..end]);
+                let line = line.trim_end_matches('\r');
+                let row = if is_csv {
+                    parse_csv_line(line, &headers)
+                } else {
+                    parse_jsonl_line(line)
+                };
+
+                let mut parsed = BTreeMap::new();
+                self.schema.apply(&row, &mut parsed);
+                if let Some(errors) = parsed.remove("errors") {
+                    return Err(TraceLoadError::Conversion {
+                        column: "schema".to_string(),
+                        expected_format: "fields declared in the trace schema".to_string(),
+                        raw: errors,
+                    });
+                }
+                if let Some(timestamp) = parsed.get("timestamp").and_then(|v| v.parse::<i64>().ok())
+                {
+                    if self.start_time == 0 {
+                        self.start_time = timestamp as u64;
+                    }
+                    index.push(RecordIndexEntry {
+                        offset,
+                        len: end - offset,
+                        timestamp: (timestamp as u64).saturating_sub(self.start_time),
+                    });
                 }
+            }
+            offset = end + 1;
+        }
 
-                // tracing::info!("waiting for write lock, index = {}", index);
-                self.rwlock.write_lock();
-                (&mut *self.user_prompts.get()).extend(new_prompts);
-                self.rwlock.write_unlock();
-                let end_time = generate_start_time.elapsed().as_millis();
-                metrics.generate_time = Some(end_time as u64);
+        self.storage = TraceStorage::Streaming(StreamingTrace {
+            mmap,
+            format: if is_csv {
+                StreamingFormat::Csv { headers }
+            } else {
+                StreamingFormat::Jsonl
+            },
+            index,
+        });
+        Ok(())
+    }
+
+    /// Like `load_eager`, but skips and counts records whose schema conversion fails or that
+    /// lack a required field, instead of failing the whole load. A line that is not valid JSON,
+    /// or a CSV record that cannot be read, still fails the whole load as in `load_eager`. Only
+    /// reachable for `GenericTrace` (the schema-driven loader); the hand-written
+    /// `BailianDataset`/`MooncakeDataset`/`AzureDataset` loaders have no such opt-in, consistent
+    /// with `GenericTrace` being the one loader meant to be pointed at arbitrary trace files.
+    pub fn load_tolerant(&mut self, path: &str) -> Result<LoadReport, TraceLoadError> {
+        let rows = if path.ends_with(".csv") {
+            load_csv_rows(path)?
+        } else {
+            load_jsonl_rows(path)?
+        };
+
+        let mut report = LoadReport::default();
+        let mut items = Vec::new();
+        for row in rows {
+            let mut parsed = BTreeMap::new();
+            self.schema.apply(&row, &mut parsed);
+            if let Some(errors) = parsed.get("errors") {
+                tracing::warn!("skipping record with schema errors: {errors}");
+                report.skipped += 1;
+                continue;
             }
-            // postcond: self.rwlock is unlocked
-            // tracing::info!("generating last block of length {}", last_block_len);
-            let post_sample_time = std::time::Instant::now();
-            if last_block_len != 0 {
-                let last_block_prompt = ts.gen_string(last_block_len);
-                prompt.push_str(&last_block_prompt);
+
+            let (Some(timestamp), Some(input_length), Some(output_length)) = (
+                parsed.get("timestamp").and_then(|v| v.parse::<i64>().ok()),
+                parsed.get("input_length").and_then(|v| v.parse::<u64>().ok()),
+                parsed.get("output_length").and_then(|v| v.parse::<u64>().ok()),
+            ) else {
+                tracing::warn!("skipping record missing required fields");
+                report.skipped += 1;
+                continue;
+            };
+            let hash_ids = parsed
+                .get("hash_ids")
+                .and_then(|v| serde_json::from_str::<Vec<u64>>(v).ok());
+
+            if self.start_time == 0 {
+                self.start_time = timestamp as u64;
             }
-            // self.rwlock.read_lock();
-            // if let Some(s) = &(&(*self.user_prompts_map.get())).get(&last_block_len) {
-            //     prompt.push_str(s);
-            //     self.rwlock.read_unlock();
-            // } else {
-            //     self.rwlock.read_unlock();
-            //     let last_block_prompt = ts.gen_string(last_block_len);
-            //     prompt.push_str(&last_block_prompt);
-            //     self.rwlock.write_lock();
-            //     (&mut *self.user_prompts_map.get()).insert(last_block_len, last_block_prompt);
-            //     self.rwlock.write_unlock();
-            // }
-
-            let end_time = inflate_start_time.elapsed().as_millis();
-            metrics.inflate_time = Some(end_time as u64);
-            metrics.post_sample_time = Some(post_sample_time.elapsed().as_millis() as u64);
-            (prompt, context_tokens, generated_tokens, metrics)
+            items.push(GenericTraceItem {
+                timestamp: (timestamp as u64).saturating_sub(self.start_time),
+                input_length,
+                output_length,
+                hash_ids,
+            });
+            report.loaded += 1;
         }
+        self.storage = TraceStorage::Eager(items);
+        tracing::info!(
+            "load_tolerant: loaded {} record(s), skipped {}",
+            report.loaded,
+            report.skipped
+        );
+        Ok(report)
     }
 }
+
+/// Outcome of [`GenericTrace::load_tolerant`]: how many records made it in vs. were skipped for
+/// failing schema conversion or missing a required field.
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    pub loaded: usize,
+    pub skipped: usize,
+}
+
 #[cfg(test)]
 mod tests {
     // use super::*;
@@ -0,0 +1,245 @@
+//! Optional MPMC dispatch subsystem built on `crossbeam::channel` (the same crate
+//! [`crate::token_sampler::TokenSampler`] already uses for its producer/consumer channels), as an
+//! alternative to the per-request `tokio::spawn` fan-out in [`crate::requester`]. Where the
+//! `spawn_request_loop*` functions let every request become its own async task with no ceiling on
+//! how many are in flight, [`Dispatcher`] bounds the queue up front and caps concurrency to a
+//! fixed worker count, trading unbounded fan-out for explicit, measurable backpressure.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crossbeam::channel::{self, select, Receiver, Sender};
+
+use crate::timeout_secs_upon_slo;
+
+/// One unit of dispatch work. `run` does the actual request send and response parsing
+/// synchronously from the worker thread's point of view (an async implementation can block on
+/// its own runtime handle inside the closure); `output_length` drives the per-job SLO deadline via
+/// [`timeout_secs_upon_slo`].
+pub struct DispatchJob {
+    pub data_index: usize,
+    pub output_length: u64,
+    pub response_sender: flume::Sender<BTreeMap<String, String>>,
+    pub run: Box<dyn FnOnce() -> BTreeMap<String, String> + Send>,
+}
+
+/// Counts surfaced by [`Dispatcher::stats`] so load-shedding under overload is observable instead
+/// of silent.
+#[derive(Debug, Default)]
+pub struct DispatchStats {
+    pub completed: AtomicU64,
+    pub timed_out: AtomicU64,
+    pub dropped: AtomicU64,
+    /// A job blew its `timeout_secs_upon_slo` deadline, so the helper thread still running it
+    /// was handed off to [`Dispatcher`]'s overflow list instead of being joined inline.
+    pub overflowed: AtomicU64,
+}
+
+/// A bounded work queue feeding `num_workers` worker threads. [`Dispatcher::try_submit`]
+/// backpressures the caller (instead of queueing unboundedly) once the channel fills up, and each
+/// worker cancels waiting on a job that blows its `timeout_secs_upon_slo` budget rather than
+/// blocking on it indefinitely.
+pub struct Dispatcher {
+    job_tx: Sender<DispatchJob>,
+    workers: Vec<JoinHandle<()>>,
+    /// Helper threads still running a job past its `timeout_secs_upon_slo` deadline (see
+    /// [`worker_loop`]), handed off here instead of being detached so [`Dispatcher::shutdown`]
+    /// can still join them rather than leaking one OS thread per timed-out job.
+    overflow: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    stats: Arc<DispatchStats>,
+}
+
+impl Dispatcher {
+    /// Spawn `num_workers` worker threads pulling from a channel bounded to `queue_capacity`.
+    pub fn new(num_workers: usize, queue_capacity: usize) -> Self {
+        let (job_tx, job_rx) = channel::bounded::<DispatchJob>(queue_capacity);
+        let stats = Arc::new(DispatchStats::default());
+        let overflow = Arc::new(Mutex::new(Vec::new()));
+
+        let workers = (0..num_workers)
+            .map(|_| {
+                let job_rx = job_rx.clone();
+                let stats = stats.clone();
+                let overflow = overflow.clone();
+                thread::spawn(move || worker_loop(job_rx, stats, overflow))
+            })
+            .collect();
+
+        Self {
+            job_tx,
+            workers,
+            overflow,
+            stats,
+        }
+    }
+
+    /// Queue a job, blocking until there's room. Returns the job back if every worker has already
+    /// shut down and the channel is disconnected.
+    pub fn submit(&self, job: DispatchJob) -> Result<(), DispatchJob> {
+        self.job_tx.send(job).map_err(|err| err.into_inner())
+    }
+
+    /// Like [`Dispatcher::submit`], but gives up instead of blocking if the queue is full right
+    /// now, counting the job as `dropped`. This is the actual backpressure knob: a generator that
+    /// would rather shed load than stall its arrival-rate distribution waiting for queue space
+    /// uses this instead of `submit`.
+    pub fn try_submit(&self, job: DispatchJob) -> Result<(), DispatchJob> {
+        self.job_tx.try_send(job).map_err(|err| {
+            self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+            match err {
+                channel::TrySendError::Full(job) => job,
+                channel::TrySendError::Disconnected(job) => job,
+            }
+        })
+    }
+
+    pub fn stats(&self) -> Arc<DispatchStats> {
+        self.stats.clone()
+    }
+
+    /// Stop accepting new work, wait for every worker to drain the queue and exit, then join
+    /// whatever overflow threads (jobs that blew their deadline) are still outstanding.
+    pub fn shutdown(self) {
+        drop(self.job_tx);
+        for worker in self.workers {
+            let _ = worker.join();
+        }
+        for overflow in self.overflow.lock().unwrap().drain(..) {
+            let _ = overflow.join();
+        }
+    }
+}
+
+fn worker_loop(
+    job_rx: Receiver<DispatchJob>,
+    stats: Arc<DispatchStats>,
+    overflow: Arc<Mutex<Vec<JoinHandle<()>>>>,
+) {
+    while let Ok(job) = job_rx.recv() {
+        let DispatchJob {
+            data_index,
+            output_length,
+            response_sender,
+            run,
+        } = job;
+
+        let deadline = Duration::from_secs(timeout_secs_upon_slo(output_length));
+        let (done_tx, done_rx) = channel::bounded::<BTreeMap<String, String>>(1);
+        // `run` may itself block well past `deadline` (e.g. a slow HTTP response); it keeps
+        // running on this helper thread in the background so the worker below isn't stuck behind
+        // it. The handle is handed off to `overflow` (reaped opportunistically below, and joined
+        // on `Dispatcher::shutdown`) rather than detached, so a pile-up of slow jobs doesn't leak
+        // OS threads for the lifetime of the process.
+        let helper = thread::spawn(move || {
+            let _ = done_tx.send(run());
+        });
+
+        select! {
+            recv(done_rx) -> result => {
+                if let Ok(metrics) = result {
+                    stats.completed.fetch_add(1, Ordering::Relaxed);
+                    let _ = response_sender.send(metrics);
+                }
+                // The helper already finished (that's how `result` arrived), so joining it here
+                // is instant instead of adding it to `overflow`.
+                let _ = helper.join();
+            }
+            default(deadline) => {
+                stats.timed_out.fetch_add(1, Ordering::Relaxed);
+                stats.overflowed.fetch_add(1, Ordering::Relaxed);
+                let mut metrics = BTreeMap::new();
+                metrics.insert("status".to_string(), "timeout".to_string());
+                metrics.insert("data_index".to_string(), data_index.to_string());
+                let _ = response_sender.send(metrics);
+
+                let mut overflow = overflow.lock().unwrap();
+                overflow.retain(|handle| !handle.is_finished());
+                overflow.push(helper);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(
+        data_index: usize,
+        output_length: u64,
+        response_sender: flume::Sender<BTreeMap<String, String>>,
+        run: impl FnOnce() -> BTreeMap<String, String> + Send + 'static,
+    ) -> DispatchJob {
+        DispatchJob {
+            data_index,
+            output_length,
+            response_sender,
+            run: Box::new(run),
+        }
+    }
+
+    #[test]
+    fn completed_job_reports_its_result_and_increments_completed() {
+        let dispatcher = Dispatcher::new(2, 8);
+        let (response_tx, response_rx) = flume::unbounded();
+
+        dispatcher
+            .submit(job(0, 10, response_tx, || {
+                let mut metrics = BTreeMap::new();
+                metrics.insert("status".to_string(), "ok".to_string());
+                metrics
+            }))
+            .unwrap();
+
+        let metrics = response_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(metrics.get("status"), Some(&"ok".to_string()));
+
+        let stats = dispatcher.stats();
+        dispatcher.shutdown();
+        assert_eq!(stats.completed.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.timed_out.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn try_submit_sheds_load_once_the_queue_is_full() {
+        // A dispatcher with no workers keeps no receiver, so its channel is already disconnected.
+        // Instead the single worker is kept busy on a job that waits for `release_tx`, so the
+        // next job fills the single queue slot and every later `try_submit` must back off
+        // instead of blocking.
+        let dispatcher = Dispatcher::new(1, 1);
+        let (response_tx, _response_rx) = flume::unbounded();
+        let (started_tx, started_rx) = channel::bounded::<()>(1);
+        let (release_tx, release_rx) = channel::bounded::<()>(0);
+
+        dispatcher
+            .submit(job(0, 10, response_tx.clone(), move || {
+                let _ = started_tx.send(());
+                let _ = release_rx.recv();
+                BTreeMap::new()
+            }))
+            .unwrap();
+        started_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        dispatcher
+            .try_submit(job(1, 10, response_tx.clone(), BTreeMap::new))
+            .unwrap();
+        let rejected = dispatcher.try_submit(job(2, 10, response_tx, BTreeMap::new));
+
+        assert!(rejected.is_err());
+        assert_eq!(dispatcher.stats().dropped.load(Ordering::Relaxed), 1);
+        drop(release_tx);
+    }
+
+    #[test]
+    fn shutdown_joins_every_worker_thread() {
+        let dispatcher = Dispatcher::new(4, 8);
+        dispatcher.shutdown();
+    }
+}
@@ -1,15 +1,50 @@
+pub mod adaptive;
+pub mod auth;
 pub mod dataset;
+pub mod dispatch;
 pub mod distribution;
 pub mod apis;
+pub mod live_metrics;
+pub mod metrics;
+pub mod protocols;
+pub mod rate_limiter;
 pub mod requester;
+pub mod response_schema;
+pub mod retry;
+mod sync;
 pub mod token_sampler;
+pub mod trace_schema;
+pub mod tui;
 
 use core::hint::spin_loop;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::thread::yield_now;
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+#[cfg(not(loom))]
+use std::sync::Mutex;
+#[cfg(not(loom))]
+use std::thread::{self, Thread};
+#[cfg(not(loom))]
+use std::time::Duration;
 
+use sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use sync::yield_now;
 use tracing::{instrument, Level};
 
+/// Spin budget before a lock stops busy-waiting and parks the thread instead — see
+/// [`SpinLock::park`]/[`SpinRwLock::park`]. Not meaningful under `loom`, which doesn't model OS
+/// thread parking, so those builds just keep spinning (`loom::model` still explores every
+/// interleaving of the CAS loops below).
+#[cfg(not(loom))]
+const SPIN_PARK_THRESHOLD: u32 = 128;
+#[cfg(loom)]
+const SPIN_PARK_THRESHOLD: u32 = u32::MAX;
+
+/// How long a parked thread waits before re-checking the lock on its own, as a backstop against a
+/// missed `unpark` (e.g. racing with the waiter-list `Mutex`). The fast, uncontended path never
+/// touches this — only threads that already exhausted [`SPIN_PARK_THRESHOLD`] pay for it.
+#[cfg(not(loom))]
+const PARK_TIMEOUT: Duration = Duration::from_millis(1);
+
 pub const TTFT: f32 = 5.;   // 5s
 pub const TPOT: f32 = 0.06; // 60ms
 
@@ -17,32 +52,74 @@ pub fn timeout_secs_upon_slo(output_length: u64) -> u64 {
     15.max((TTFT + TPOT * output_length as f32) as u64)
 }
 
-/// Light weighted spinlock, for extremely short critical section
-/// do not abuse it
-pub struct SpinLock {
+/// Linear-interpolated percentile over an already-sorted slice. Shared by every
+/// `request_sim::protocols`/`request_sim::apis` implementor that reports latency percentiles.
+pub fn percentile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.len() == 1 {
+        return sorted[0];
+    }
+    let rank = q * (sorted.len() - 1) as f64;
+    let lo = rank.floor() as usize;
+    let hi = rank.ceil() as usize;
+    if lo == hi {
+        sorted[lo]
+    } else {
+        let frac = rank - lo as f64;
+        sorted[lo] * (1.0 - frac) + sorted[hi] * frac
+    }
+}
+
+/// Light weighted spinlock, for extremely short critical section, do not abuse it. Unlike
+/// `std::sync::Mutex`, guards the data it protects directly (like the `spin` crate's `Mutex<T>`)
+/// instead of leaving callers to pair up manual `lock`/`unlock` calls around a bare `UnsafeCell`.
+pub struct SpinLock<T> {
     flag: AtomicBool, // false: unlocked, true: locked
+    /// Set while at least one thread is parked in [`SpinLock::park`], so the fast uncontended
+    /// unlock path (no parked waiters) never has to touch `waiters`.
+    #[cfg(not(loom))]
+    parked: AtomicBool,
+    #[cfg(not(loom))]
+    waiters: Mutex<Vec<Thread>>,
+    data: UnsafeCell<T>,
 }
 
-unsafe impl Send for SpinLock {}
-unsafe impl Sync for SpinLock {}
+unsafe impl<T: Send> Send for SpinLock<T> {}
+unsafe impl<T: Send> Sync for SpinLock<T> {}
 
-#[allow(unused)]
-impl SpinLock {
-    pub const fn new() -> Self {
+impl<T> SpinLock<T> {
+    // `loom`'s atomics aren't `const fn`-constructible (they carry extra bookkeeping for the
+    // model checker), so this is only `const` on the real-atomics build.
+    #[cfg(not(loom))]
+    pub const fn new(data: T) -> Self {
         Self {
             flag: AtomicBool::new(false),
+            parked: AtomicBool::new(false),
+            waiters: Mutex::new(Vec::new()),
+            data: UnsafeCell::new(data),
         }
     }
 
-    /// 阻塞式获取锁（自旋）
-    pub fn lock(&self) {
+    #[cfg(loom)]
+    pub fn new(data: T) -> Self {
+        Self {
+            flag: AtomicBool::new(false),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// 阻塞式获取锁（自旋，超过 [`SPIN_PARK_THRESHOLD`] 后转为 park）
+    pub fn lock(&self) -> SpinGuard<'_, T> {
         // test-and-test-and-set + 退避
         let mut spins = 0u32;
         loop {
             // 快路径：先“读”观察是否可能解锁（避免频繁写入导致的总线抖动）
             while self.flag.load(Ordering::Relaxed) {
-                // 小退避：短自旋
-                spins = backoff(spins);
+                if spins >= SPIN_PARK_THRESHOLD {
+                    self.park();
+                    spins = 0;
+                } else {
+                    spins = backoff(spins);
+                }
             }
 
             // 真正尝试：CAS 抢锁
@@ -54,17 +131,82 @@ impl SpinLock {
             ) {
                 Ok(_) => break,
                 Err(_) => {
-                    // 失败则继续退避
-                    spins = backoff(spins);
+                    // 失败则继续退避，耗尽自旋预算后 park
+                    if spins >= SPIN_PARK_THRESHOLD {
+                        self.park();
+                        spins = 0;
+                    } else {
+                        spins = backoff(spins);
+                    }
                 }
             }
         }
+        SpinGuard { lock: self }
+    }
+
+    /// Register the current thread as a waiter and block until [`SpinGuard::drop`] wakes it (or
+    /// [`PARK_TIMEOUT`] elapses, as a backstop). Only called after the spin budget is exhausted.
+    #[cfg(not(loom))]
+    fn park(&self) {
+        {
+            let mut waiters = self.waiters.lock().unwrap();
+            waiters.push(thread::current());
+            self.parked.store(true, Ordering::Release);
+        }
+        // Re-check after registering: the unlocker may have already run between our last failed
+        // CAS and here, in which case there's nothing to wait for.
+        if !self.flag.load(Ordering::Relaxed) {
+            return;
+        }
+        thread::park_timeout(PARK_TIMEOUT);
+    }
+
+    /// `loom` doesn't model OS-level thread parking, so the loom build just spins — `loom::model`
+    /// already exhaustively explores every interleaving of the CAS loop in [`SpinLock::lock`].
+    #[cfg(loom)]
+    fn park(&self) {}
+
+    #[cfg(not(loom))]
+    fn wake_one(&self) {
+        if !self.parked.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut waiters = self.waiters.lock().unwrap();
+        if let Some(t) = waiters.pop() {
+            if waiters.is_empty() {
+                self.parked.store(false, Ordering::Relaxed);
+            }
+            t.unpark();
+        }
     }
 
-    #[inline]
-    fn unlock(&self) {
+    #[cfg(loom)]
+    fn wake_one(&self) {}
+}
+
+/// RAII guard returned by [`SpinLock::lock`]; releases the lock when dropped.
+pub struct SpinGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+}
+
+impl<'a, T> Deref for SpinGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for SpinGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for SpinGuard<'a, T> {
+    fn drop(&mut self) {
         // 释放锁：Release 保证写入 data 对后继获取者可见
-        self.flag.store(false, Ordering::Release);
+        self.lock.flag.store(false, Ordering::Release);
+        self.lock.wake_one();
     }
 }
 
@@ -87,35 +229,93 @@ fn backoff(spins: u32) -> u32 {
     }
 }
 
-unsafe impl Send for SpinRwLock {}
-unsafe impl Sync for SpinRwLock {}
+unsafe impl<T: Send> Send for SpinRwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for SpinRwLock<T> {}
 
-pub struct SpinRwLock {
+/// Same idea as [`SpinLock`], but with the reader/writer state machine a `spin::RwLock` has:
+/// `read_lock()`/`write_lock()` return [`SpinReadGuard`]/[`SpinWriteGuard`] guarding the data
+/// directly instead of requiring callers to pair up manual `*_lock`/`*_unlock` calls themselves.
+pub struct SpinRwLock<T> {
     state: AtomicUsize,
+    #[cfg(not(loom))]
+    waiters: Mutex<Vec<Thread>>,
+    data: UnsafeCell<T>,
 }
 
 const USIZE_BITS: u32 = (core::mem::size_of::<usize>() * 8) as u32;
 const WRITER_BIT: usize = 1usize << (USIZE_BITS - 1);
 const WAITER_BIT: usize = 1usize << (USIZE_BITS - 2);
-const READER_MASK: usize = !(WRITER_BIT | WAITER_BIT);
+/// Set whenever a thread is parked in [`SpinRwLock::park`]; `read_unlock`/`write_unlock` only
+/// take the `waiters`-`Mutex` slow path when this bit is set, so the uncontended fast path stays
+/// pure atomics.
+const PARKED_BIT: usize = 1usize << (USIZE_BITS - 3);
+const READER_MASK: usize = !(WRITER_BIT | WAITER_BIT | PARKED_BIT);
 
-impl SpinRwLock {
-    pub const fn new() -> Self {
+impl<T> SpinRwLock<T> {
+    #[cfg(not(loom))]
+    pub const fn new(data: T) -> Self {
         Self {
             state: AtomicUsize::new(0),
+            waiters: Mutex::new(Vec::new()),
+            data: UnsafeCell::new(data),
         }
     }
 
+    #[cfg(loom)]
+    pub fn new(data: T) -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Register the current thread as a waiter and block until a `read_unlock`/`write_unlock`
+    /// wakes it (or [`PARK_TIMEOUT`] elapses, as a backstop against a missed wakeup). Only called
+    /// after the spin budget is exhausted.
+    #[cfg(not(loom))]
+    fn park(&self) {
+        {
+            let mut waiters = self.waiters.lock().unwrap();
+            waiters.push(thread::current());
+            self.state.fetch_or(PARKED_BIT, Ordering::Release);
+        }
+        thread::park_timeout(PARK_TIMEOUT);
+    }
+
+    /// `loom` doesn't model OS-level thread parking; the loom build keeps spinning so
+    /// `loom::model` still explores every interleaving of `read_lock`/`write_lock`.
+    #[cfg(loom)]
+    fn park(&self) {}
+
+    #[cfg(not(loom))]
+    fn wake_one(&self) {
+        let mut waiters = self.waiters.lock().unwrap();
+        if let Some(t) = waiters.pop() {
+            if waiters.is_empty() {
+                self.state.fetch_and(!PARKED_BIT, Ordering::Relaxed);
+            }
+            t.unpark();
+        }
+    }
+
+    #[cfg(loom)]
+    fn wake_one(&self) {}
+
     /// Get read lock, while writer is priorized
     #[instrument(skip_all, level = Level::DEBUG, target = "spin_rwlck::read")]
-    pub fn read_lock(&self) {
+    pub fn read_lock(&self) -> SpinReadGuard<'_, T> {
         let mut spins = 0u32;
         loop {
             let s = self.state.load(Ordering::Relaxed);
             if s & (WRITER_BIT | WAITER_BIT) != 0 {
                 // can't acquire lock due to writer
-                spins += 1;
-                backoff(spins);
+                if spins >= SPIN_PARK_THRESHOLD {
+                    self.park();
+                    spins = 0;
+                } else {
+                    spins += 1;
+                    backoff(spins);
+                }
                 continue;
             }
             if self
@@ -129,23 +329,31 @@ impl SpinRwLock {
                 .is_ok()
             {
                 // acquire read lock, add reader counter
-                return;
+                return SpinReadGuard { lock: self };
             }
             // can't acquire lock due to writer
-            spins += 1;
-            backoff(spins);
+            if spins >= SPIN_PARK_THRESHOLD {
+                self.park();
+                spins = 0;
+            } else {
+                spins += 1;
+                backoff(spins);
+            }
         }
     }
 
-    pub fn read_unlock(&self) {
+    fn read_unlock(&self) {
         // sub reader counter
         let prev = self.state.fetch_sub(1, Ordering::Release);
         debug_assert!(prev & READER_MASK >= 1);
+        if prev & PARKED_BIT != 0 {
+            self.wake_one();
+        }
     }
 
     /// Get write lock, while writer is priorized
     #[instrument(skip_all, level = Level::DEBUG, target = "spin_rwlck::write")]
-    pub fn write_lock(&self) {
+    pub fn write_lock(&self) -> SpinWriteGuard<'_, T> {
         let mut spins = 0u32;
         // mark self as waiter
         loop {
@@ -164,8 +372,11 @@ impl SpinRwLock {
                     // self is the waiter now
                     break;
                 }
-            } else {
+            } else if spins >= SPIN_PARK_THRESHOLD {
                 // other writer is the waiter, wait for write lock
+                self.park();
+                spins = 0;
+            } else {
                 spins += 1;
                 backoff(spins);
             }
@@ -175,30 +386,164 @@ impl SpinRwLock {
         loop {
             let s = self.state.load(Ordering::Relaxed);
             if s & READER_MASK == 0 && s & WRITER_BIT == 0 {
-                // precond: self is the write lock waiter
-                // no readers hold lock, no writer holds lock
+                // precond: self is the write lock waiter. no readers hold lock, no writer holds
+                // lock. Keep whatever PARKED_BIT is currently set (a reader may have parked while
+                // we were waiting) rather than assuming `s == WAITER_BIT`.
                 if self
                     .state
                     .compare_exchange(
-                        WAITER_BIT,
-                        WRITER_BIT,
+                        s,
+                        WRITER_BIT | (s & PARKED_BIT),
                         Ordering::Acquire,
                         Ordering::Relaxed,
                     )
                     .is_ok()
                 {
                     // acquire writer lock, set writer bit
-                    return;
+                    return SpinWriteGuard { lock: self };
                 }
             }
-            spins += 1;
-            backoff(spins);
+            if spins >= SPIN_PARK_THRESHOLD {
+                self.park();
+                spins = 0;
+            } else {
+                spins += 1;
+                backoff(spins);
+            }
         }
     }
 
-    pub fn write_unlock(&self) {
-        // clear writer bit
-        let prev = self.state.swap(0, Ordering::Release);
+    fn write_unlock(&self) {
+        // clear writer bit, but preserve PARKED_BIT: `wake_one` is the only one that clears it,
+        // once the waiter list it guards has actually drained.
+        let prev = self.state.fetch_and(!WRITER_BIT, Ordering::Release);
         debug_assert!(prev & WRITER_BIT != 0);
+        if prev & PARKED_BIT != 0 {
+            self.wake_one();
+        }
+    }
+}
+
+/// RAII guard returned by [`SpinRwLock::read_lock`]; releases the read lock when dropped.
+pub struct SpinReadGuard<'a, T> {
+    lock: &'a SpinRwLock<T>,
+}
+
+impl<'a, T> Deref for SpinReadGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for SpinReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.read_unlock();
+    }
+}
+
+/// RAII guard returned by [`SpinRwLock::write_lock`]; releases the write lock when dropped.
+pub struct SpinWriteGuard<'a, T> {
+    lock: &'a SpinRwLock<T>,
+}
+
+impl<'a, T> Deref for SpinWriteGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for SpinWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for SpinWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.write_unlock();
+    }
+}
+
+/// Model-checked concurrency tests for [`SpinRwLock`], run under `loom::model` instead of real
+/// threads so every possible interleaving of the weak-CAS loops in `read_lock`/`write_lock` is
+/// explored, not just whatever the OS scheduler happens to hit. Run with
+/// `RUSTFLAGS="--cfg loom" cargo test --release --features loom --lib -- --nocapture` (loom
+/// exhaustively explores schedules, so these are far slower than ordinary unit tests and stay
+/// behind the `loom` feature rather than running by default).
+#[cfg(loom)]
+#[cfg(test)]
+mod loom_tests {
+    use loom::sync::Arc;
+    use loom::thread;
+
+    use super::{SpinRwLock, READER_MASK, WRITER_BIT};
+
+    /// 2 readers + 1 writer: no reader ever observes `WRITER_BIT` set while holding its read
+    /// lock, and the writer's increment is never lost.
+    #[test]
+    fn two_readers_one_writer_no_lost_write() {
+        loom::model(|| {
+            let lock = Arc::new(SpinRwLock::new(0usize));
+
+            let readers: Vec<_> = (0..2)
+                .map(|_| {
+                    let lock = Arc::clone(&lock);
+                    thread::spawn(move || {
+                        let guard = lock.read_lock();
+                        let s = lock.state.load(loom::sync::atomic::Ordering::Relaxed);
+                        assert_eq!(s & WRITER_BIT, 0, "reader saw WRITER_BIT set while holding read_lock");
+                        drop(guard);
+                    })
+                })
+                .collect();
+
+            let writer = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    let mut guard = lock.write_lock();
+                    *guard += 1;
+                })
+            };
+
+            for r in readers {
+                r.join().unwrap();
+            }
+            writer.join().unwrap();
+
+            assert_eq!(*lock.read_lock(), 1);
+        });
+    }
+
+    /// 2 writers racing: both increments land, i.e. the mutual-exclusion invariant (reader count
+    /// is zero whenever `WRITER_BIT` is set) holds regardless of interleaving.
+    #[test]
+    fn two_writers_no_lost_update() {
+        loom::model(|| {
+            let lock = Arc::new(SpinRwLock::new(0usize));
+
+            let writers: Vec<_> = (0..2)
+                .map(|_| {
+                    let lock = Arc::clone(&lock);
+                    thread::spawn(move || {
+                        let mut guard = lock.write_lock();
+                        let s = lock.state.load(loom::sync::atomic::Ordering::Relaxed);
+                        assert_eq!(
+                            s & READER_MASK,
+                            0,
+                            "writer held the lock while a reader count was still nonzero"
+                        );
+                        *guard += 1;
+                    })
+                })
+                .collect();
+
+            for w in writers {
+                w.join().unwrap();
+            }
+
+            assert_eq!(*lock.read_lock(), 2);
+        });
     }
 }
@@ -0,0 +1,331 @@
+//! Run-to-date latency aggregates exposed over HTTP while a `report_loop` is running. Every
+//! counter and quantile covers all results since the run started; no window ages samples out.
+//!
+//! This mirrors the admin/metrics server pattern used by Garage: a tiny HTTP server runs
+//! alongside the main workload, fed by the same stream of results, and exposes whatever it has
+//! accumulated so far rather than requiring the run to finish first.
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{extract::State, routing::get, Json, Router};
+use tokio::{net::TcpListener, task::JoinHandle};
+
+/// Streaming quantile estimator using Jain & Chlamtac's P² algorithm: O(1) memory per quantile,
+/// independent of how many samples have been observed.
+#[derive(Debug, Clone)]
+struct P2Quantile {
+    p: f64,
+    n: [f64; 5],
+    q: [f64; 5],
+    initial: Vec<f64>,
+}
+
+impl P2Quantile {
+    fn new(p: f64) -> Self {
+        Self {
+            p,
+            n: [1.0, 2.0, 3.0, 4.0, 5.0],
+            q: [0.0; 5],
+            initial: Vec::with_capacity(5),
+        }
+    }
+
+    fn observe(&mut self, x: f64) {
+        if self.initial.len() < 5 {
+            self.initial.push(x);
+            if self.initial.len() == 5 {
+                self.initial.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                self.q.copy_from_slice(&self.initial);
+            }
+            return;
+        }
+
+        let dn = [0.0, self.p / 2.0, self.p, (1.0 + self.p) / 2.0, 1.0];
+
+        let k = if x < self.q[0] {
+            self.q[0] = x;
+            0
+        } else if x >= self.q[4] {
+            self.q[4] = x;
+            3
+        } else {
+            (0..4).find(|&i| x < self.q[i + 1]).unwrap()
+        };
+        for i in (k + 1)..5 {
+            self.n[i] += 1.0;
+        }
+        for i in 1..4 {
+            let d = dn[i] * (self.n[4] - self.n[0]) + self.n[0] - self.n[i];
+            if (d >= 1.0 && self.n[i + 1] - self.n[i] > 1.0)
+                || (d <= -1.0 && self.n[i - 1] - self.n[i] < -1.0)
+            {
+                let d = d.signum();
+                let qi = self.parabolic(i, d);
+                if self.q[i - 1] < qi && qi < self.q[i + 1] {
+                    self.q[i] = qi;
+                } else {
+                    self.q[i] = self.linear(i, d);
+                }
+                self.n[i] += d;
+            }
+        }
+    }
+
+    fn parabolic(&self, i: usize, d: f64) -> f64 {
+        self.q[i]
+            + d / (self.n[i + 1] - self.n[i - 1])
+                * ((self.n[i] - self.n[i - 1] + d) * (self.q[i + 1] - self.q[i])
+                    / (self.n[i + 1] - self.n[i])
+                    + (self.n[i + 1] - self.n[i] - d) * (self.q[i] - self.q[i - 1])
+                        / (self.n[i] - self.n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, d: f64) -> f64 {
+        let j = (i as i64 + d as i64) as usize;
+        self.q[i] + d * (self.q[j] - self.q[i]) / (self.n[j] - self.n[i])
+    }
+
+    fn value(&self) -> f64 {
+        if self.initial.len() < 5 {
+            let mut sorted = self.initial.clone();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let idx = ((sorted.len() as f64 - 1.0) * self.p).round() as usize;
+            return sorted.get(idx).copied().unwrap_or(0.0);
+        }
+        self.q[2]
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MetricQuantiles {
+    p50: P2Quantile,
+    p90: P2Quantile,
+    p95: P2Quantile,
+    p99: P2Quantile,
+}
+
+impl MetricQuantiles {
+    fn new() -> Self {
+        Self {
+            p50: P2Quantile::new(0.50),
+            p90: P2Quantile::new(0.90),
+            p95: P2Quantile::new(0.95),
+            p99: P2Quantile::new(0.99),
+        }
+    }
+
+    fn observe(&mut self, x: f64) {
+        self.p50.observe(x);
+        self.p90.observe(x);
+        self.p95.observe(x);
+        self.p99.observe(x);
+    }
+
+    fn snapshot(&self) -> QuantileSnapshot {
+        QuantileSnapshot {
+            p50: self.p50.value(),
+            p90: self.p90.value(),
+            p95: self.p95.value(),
+            p99: self.p99.value(),
+        }
+    }
+}
+
+/// Point-in-time read of a [`MetricQuantiles`], cheap to copy out of the lock for a TUI/report
+/// redraw.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuantileSnapshot {
+    pub p50: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+/// Cumulative aggregates fed by `report_loop` as results arrive. They cover every result since
+/// the run started; old samples never age out.
+#[derive(Debug)]
+pub struct LiveMetrics {
+    request_count: u64,
+    success_count: u64,
+    error_count: u64,
+    in_flight: i64,
+    first_token_time: MetricQuantiles,
+    total_time: MetricQuantiles,
+    queue_time: MetricQuantiles,
+    inference_time: MetricQuantiles,
+}
+
+impl LiveMetrics {
+    fn new() -> Self {
+        Self {
+            request_count: 0,
+            success_count: 0,
+            error_count: 0,
+            in_flight: 0,
+            first_token_time: MetricQuantiles::new(),
+            total_time: MetricQuantiles::new(),
+            queue_time: MetricQuantiles::new(),
+            inference_time: MetricQuantiles::new(),
+        }
+    }
+
+    /// Feed one completed/aborted request's metrics map into the aggregates.
+    pub fn record(&mut self, metrics: &BTreeMap<String, String>) {
+        self.request_count += 1;
+        self.in_flight = (self.in_flight - 1).max(0);
+
+        let is_success = metrics
+            .get("status")
+            .map(|s| s.starts_with('2'))
+            .unwrap_or(true);
+        if is_success {
+            self.success_count += 1;
+        } else {
+            self.error_count += 1;
+        }
+
+        if let Some(v) = metrics
+            .get("first_token_time")
+            .and_then(|v| v.parse::<f64>().ok())
+        {
+            self.first_token_time.observe(v);
+        }
+        if let Some(v) = metrics
+            .get("total_time")
+            .and_then(|v| v.parse::<f64>().ok())
+        {
+            self.total_time.observe(v);
+        }
+        if let Some(v) = metrics
+            .get("queue_time")
+            .and_then(|v| v.parse::<f64>().ok())
+        {
+            self.queue_time.observe(v);
+        }
+        if let Some(v) = metrics
+            .get("inference_time")
+            .and_then(|v| v.parse::<f64>().ok())
+        {
+            self.inference_time.observe(v);
+        }
+    }
+
+    /// Called when a new request is dispatched, before its result is known.
+    pub fn record_dispatch(&mut self) {
+        self.in_flight += 1;
+    }
+
+    /// Cheap point-in-time copy of the counters/quantiles, for a TUI redraw loop that can't hold
+    /// the lock across an `await`.
+    pub fn snapshot(&self) -> LiveMetricsSnapshot {
+        LiveMetricsSnapshot {
+            request_count: self.request_count,
+            success_count: self.success_count,
+            error_count: self.error_count,
+            in_flight: self.in_flight,
+            first_token_time: self.first_token_time.snapshot(),
+            total_time: self.total_time.snapshot(),
+            queue_time: self.queue_time.snapshot(),
+            inference_time: self.inference_time.snapshot(),
+        }
+    }
+
+    fn prometheus_text(&self) -> String {
+        format!(
+            "# HELP request_sim_requests_total Total requests completed\n\
+             # TYPE request_sim_requests_total counter\n\
+             request_sim_requests_total {}\n\
+             request_sim_requests_success {}\n\
+             request_sim_requests_error {}\n\
+             request_sim_in_flight {}\n\
+             request_sim_first_token_time_p90 {}\n\
+             request_sim_first_token_time_p95 {}\n\
+             request_sim_first_token_time_p99 {}\n\
+             request_sim_total_time_p90 {}\n\
+             request_sim_total_time_p95 {}\n\
+             request_sim_total_time_p99 {}\n",
+            self.request_count,
+            self.success_count,
+            self.error_count,
+            self.in_flight,
+            self.first_token_time.p90.value(),
+            self.first_token_time.p95.value(),
+            self.first_token_time.p99.value(),
+            self.total_time.p90.value(),
+            self.total_time.p95.value(),
+            self.total_time.p99.value(),
+        )
+    }
+
+    fn stats_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "request_count": self.request_count,
+            "success_count": self.success_count,
+            "error_count": self.error_count,
+            "in_flight": self.in_flight,
+            "first_token_time": {
+                "p90": self.first_token_time.p90.value(),
+                "p95": self.first_token_time.p95.value(),
+                "p99": self.first_token_time.p99.value(),
+            },
+            "total_time": {
+                "p90": self.total_time.p90.value(),
+                "p95": self.total_time.p95.value(),
+                "p99": self.total_time.p99.value(),
+            },
+            "queue_time": {
+                "p90": self.queue_time.p90.value(),
+                "p95": self.queue_time.p95.value(),
+                "p99": self.queue_time.p99.value(),
+            },
+        })
+    }
+}
+
+/// Point-in-time read of [`LiveMetrics`]; see [`LiveMetrics::snapshot`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LiveMetricsSnapshot {
+    pub request_count: u64,
+    pub success_count: u64,
+    pub error_count: u64,
+    pub in_flight: i64,
+    pub first_token_time: QuantileSnapshot,
+    pub total_time: QuantileSnapshot,
+    pub queue_time: QuantileSnapshot,
+    pub inference_time: QuantileSnapshot,
+}
+
+pub type SharedLiveMetrics = Arc<Mutex<LiveMetrics>>;
+
+pub fn new_shared() -> SharedLiveMetrics {
+    Arc::new(Mutex::new(LiveMetrics::new()))
+}
+
+async fn metrics_handler(State(metrics): State<SharedLiveMetrics>) -> String {
+    metrics.lock().unwrap().prometheus_text()
+}
+
+async fn stats_handler(State(metrics): State<SharedLiveMetrics>) -> Json<serde_json::Value> {
+    Json(metrics.lock().unwrap().stats_json())
+}
+
+/// Start the admin HTTP server. The caller is responsible for aborting the returned handle once
+/// the run it is reporting on has finished.
+pub fn spawn_admin_server(addr: SocketAddr, metrics: SharedLiveMetrics) -> JoinHandle<()> {
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .route("/stats", get(stats_handler))
+        .with_state(metrics);
+
+    tokio::spawn(async move {
+        let listener = TcpListener::bind(addr)
+            .await
+            .expect("failed to bind admin server");
+        axum::serve(listener, app)
+            .await
+            .expect("admin server crashed");
+    })
+}
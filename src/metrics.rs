@@ -6,4 +6,11 @@ pub struct SystemMetrics {
     pub send_gap: Option<u64>,
     pub prev_sample_time: Option<u64>,
     pub post_sample_time: Option<u64>,
+    /// Number of send attempts [`crate::apis::SyncLLMClient::send_with_retry`] made for a request,
+    /// including the first one.
+    pub attempt_count: Option<u32>,
+    /// Wall-clock latency of each individual attempt, in the same order they were sent, so tail
+    /// behavior under server overload (a slow first attempt before a fast retry, say) isn't
+    /// collapsed into a single end-to-end number.
+    pub attempt_latencies_ms: Option<Vec<u64>>,
 }
@@ -1,12 +1,51 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, sync::OnceLock, time::Instant};
 
+use futures_util::StreamExt;
 use rand::{thread_rng, Rng};
 use reqwest::Response;
 use serde::{Deserialize, Serialize};
 use tokenizers::Tokenizer;
 
+use crate::percentile;
+use crate::response_schema::{Conversion, ResponseSchema};
+
 use super::Protocol;
 
+/// Schema for the `x-*` timing headers a non-streaming distserve response reports. Built once and
+/// reused across calls since it is immutable after construction.
+fn response_schema() -> &'static ResponseSchema {
+    static SCHEMA: OnceLock<ResponseSchema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        ResponseSchema::new()
+            .field("first_token_time", "x-first-token-time", Conversion::Float)
+            .field("total_time", "x-total-time", Conversion::Float)
+            .field("inference_time", "x-inference-time", Conversion::Float)
+            .field("queue_time", "x-queue-time", Conversion::Float)
+            .field(
+                "max_time_between_tokens",
+                "x-max-time-between-tokens",
+                Conversion::Float,
+            )
+            .field(
+                "p70_time_between_tokens",
+                "x-p70-time-between-tokens",
+                Conversion::Float,
+            )
+            .field(
+                "p90_time_between_tokens",
+                "x-p90-time-between-tokens",
+                Conversion::Float,
+            )
+            .field(
+                "p99_time_between_tokens",
+                "x-p99-time-between-tokens",
+                Conversion::Float,
+            )
+            .field("output_length", "x-output-length", Conversion::Integer)
+            .field("input_length", "x-input-length", Conversion::Integer)
+    })
+}
+
 pub struct DistserveProtocol {
     tokenizer: Tokenizer,
 
@@ -17,6 +56,12 @@ pub struct DistserveProtocol {
     end: u32,
 
     max_token_size: u64,
+
+    /// When set, [`Protocol::request_json_body`] asks the server to stream tokens over SSE and
+    /// [`DistserveProtocol::parse_response_streaming`] should be used instead of
+    /// [`Protocol::parse_response`], which trusts server-reported `x-*` headers that a streaming
+    /// response may not send.
+    stream_mode: bool,
 }
 
 impl DistserveProtocol {
@@ -27,6 +72,16 @@ impl DistserveProtocol {
             start: 0,
             end: 10000,
             max_token_size: 3950,
+            stream_mode: false,
+        }
+    }
+
+    /// Same as [`Self::new`], but requests a streaming (SSE) response and measures TTFT /
+    /// inter-token latency client-side via [`Self::parse_response_streaming`].
+    pub fn new_streaming(tokenizer: Tokenizer) -> Self {
+        Self {
+            stream_mode: true,
+            ..Self::new(tokenizer)
         }
     }
 }
@@ -75,122 +130,120 @@ impl Protocol for DistserveProtocol {
             "temperature": 1.0,
             "top_p": 1.0,
             "ignore_eos": true,
-            "stream": false
+            "stream": self.stream_mode
         });
         json_body.to_string()
     }
 
-    fn parse_response(response: Response) -> BTreeMap<String, String> {
+    async fn parse_response(response: Response) -> BTreeMap<String, String> {
         let mut map = BTreeMap::new();
-        println!("{:?}", response);
         map.insert("status".to_string(), response.status().as_str().to_string());
         if response.status().is_success() {
-            let first_token_time = response
-                .headers()
-                .get("x-first-token-time")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            map.insert("first_token_time".to_string(), first_token_time);
+            response_schema().apply(response.headers(), &mut map);
+        }
+        map
+    }
+}
 
-            let total_time = response
-                .headers()
-                .get("x-total-time")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            map.insert("total_time".to_string(), total_time);
+#[derive(Deserialize, Debug)]
+struct StreamToken {
+    #[serde(default)]
+    text: String,
+}
 
-            let inference_time = response
-                .headers()
-                .get("x-inference-time")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            map.insert("inference_time".to_string(), inference_time);
+impl DistserveProtocol {
+    /// Consume a streaming response and measure TTFT / inter-token latency client-side instead of
+    /// trusting the server's `x-*` headers, which a streaming response may not send at all.
+    ///
+    /// Every SSE chunk with a non-empty `text` fragment counts as one generated token. Populates
+    /// the same `BTreeMap` keys [`Protocol::parse_response`] does (`first_token_time`,
+    /// `max_time_between_tokens`, `p70/p90/p99_time_between_tokens`, `output_length`), but derived
+    /// from locally observed `Instant::now()` timestamps rather than server-reported headers.
+    pub async fn parse_response_streaming(response: Response) -> BTreeMap<String, String> {
+        let mut map = BTreeMap::new();
+        map.insert("status".to_string(), response.status().as_str().to_string());
+        if !response.status().is_success() {
+            return map;
+        }
 
-            let queue_time = response
-                .headers()
-                .get("x-queue-time")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            map.insert("queue_time".to_string(), queue_time);
+        let start = Instant::now();
+        let mut first_token_time = None;
+        let mut last_token_time = start;
+        let mut gaps = Vec::new();
+        let mut output_length = 0u64;
 
-            let max_time_between_tokens = response
-                .headers()
-                .get("x-max-time-between-tokens")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(_) => break,
+            };
+            for line in chunk.split(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(line);
+                let data = line.trim_start_matches("data:").trim();
+                if data.is_empty() || data == "[DONE]" {
+                    continue;
+                }
+
+                let has_text = serde_json::from_str::<StreamToken>(data)
+                    .ok()
+                    .map(|token| !token.text.is_empty())
+                    .unwrap_or(false);
+                if !has_text {
+                    continue;
+                }
+
+                let now = Instant::now();
+                match first_token_time {
+                    None => first_token_time = Some(now.duration_since(start)),
+                    Some(_) => {
+                        gaps.push(now.duration_since(last_token_time).as_secs_f64() * 1000.0)
+                    }
+                }
+                last_token_time = now;
+                output_length += 1;
+            }
+        }
+
+        map.insert(
+            "first_token_time".to_string(),
+            first_token_time
+                .unwrap_or_default()
+                .as_secs_f64()
+                .to_string(),
+        );
+        map.insert(
+            "total_time".to_string(),
+            start.elapsed().as_secs_f64().to_string(),
+        );
+        map.insert("output_length".to_string(), output_length.to_string());
+
+        if !gaps.is_empty() {
+            let mut sorted_gaps = gaps.clone();
+            sorted_gaps.sort_by(|a, b| a.partial_cmp(b).unwrap());
             map.insert(
                 "max_time_between_tokens".to_string(),
-                max_time_between_tokens,
+                sorted_gaps.last().copied().unwrap_or(0.0).to_string(),
             );
-
-            let p70_time_between_tokens = response
-                .headers()
-                .get("x-p70-time-between-tokens")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
             map.insert(
                 "p70_time_between_tokens".to_string(),
-                p70_time_between_tokens,
+                percentile(&sorted_gaps, 0.70).to_string(),
             );
-
-            let p90_time_between_tokens = response
-                .headers()
-                .get("x-p90-time-between-tokens")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
             map.insert(
                 "p90_time_between_tokens".to_string(),
-                p90_time_between_tokens,
+                percentile(&sorted_gaps, 0.90).to_string(),
             );
-
-            let p99_time_between_tokens = response
-                .headers()
-                .get("x-p99-time-between-tokens")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
             map.insert(
                 "p99_time_between_tokens".to_string(),
-                p99_time_between_tokens,
+                percentile(&sorted_gaps, 0.99).to_string(),
             );
-
-            let output_length = response
-                .headers()
-                .get("x-output-length")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            map.insert("output_length".to_string(), output_length);
-
-            let input_length = response
-                .headers()
-                .get("x-input-length")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            map.insert("input_length".to_string(), input_length);
         }
+
         map
     }
 }
 
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -13,11 +13,9 @@ impl Protocol for MockProtocol {
         .to_string()
     }
 
-    fn parse_response(&self) -> fn(response: reqwest::Response) -> BTreeMap<String, String> {
-        |_| -> BTreeMap<String, String> {
-            let mut map = BTreeMap::new();
-            map.insert("id".to_string(), rand::random::<u64>().to_string());
-            map
-        }
+    async fn parse_response(_response: reqwest::Response) -> BTreeMap<String, String> {
+        let mut map = BTreeMap::new();
+        map.insert("id".to_string(), rand::random::<u64>().to_string());
+        map
     }
 }
@@ -1,10 +1,265 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, future::Future, time::Instant};
 
-use reqwest::Response;
+use jsonschema::{Draft, JSONSchema};
+use reqwest::{Response, StatusCode};
+
+use crate::retry::{with_retry, RetryPolicy};
 
 pub mod tgi_api;
 
+pub mod distserve_protocol;
+pub mod mock_protocol;
+pub mod st_protocol;
+pub mod tgi_protocol;
+pub mod vllm_protocol;
+
+pub use distserve_protocol::DistserveProtocol;
+pub use mock_protocol::MockProtocol;
+pub use st_protocol::StProtocol;
+pub use tgi_protocol::TgiProtocol;
+pub use vllm_protocol::VllmProtocol;
+
+/// A constrained-decoding grammar to attach to a generation request body. Shared across protocols
+/// since TGI and vLLM both validate one client-side before rendering it into their own body shape
+/// (`grammar`/`guided_json`/`guided_regex`).
+#[derive(Debug, Clone)]
+pub enum GrammarSpec {
+    /// A JSON-schema the generated text must conform to.
+    Json(serde_json::Value),
+    /// A regex the generated text must match.
+    Regex(String),
+}
+
+impl GrammarSpec {
+    /// Validate a [`GrammarSpec::Json`] schema against draft 2019-09 so a malformed schema fails
+    /// fast here, at config-load time, instead of as an opaque server 422 once the run has
+    /// already started. [`GrammarSpec::Regex`] has nothing to validate client-side.
+    pub fn validate(&self) -> Result<(), GrammarError> {
+        match self {
+            GrammarSpec::Json(schema) => JSONSchema::options()
+                .with_draft(Draft::Draft201909)
+                .compile(schema)
+                .map(|_| ())
+                .map_err(|err| GrammarError::InvalidSchema(err.to_string())),
+            GrammarSpec::Regex(_) => Ok(()),
+        }
+    }
+}
+
+/// Why a [`GrammarSpec`] was rejected before it ever reached the server.
+#[derive(Debug)]
+pub enum GrammarError {
+    InvalidSchema(String),
+}
+
+impl std::fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GrammarError::InvalidSchema(reason) => write!(f, "invalid grammar schema: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for GrammarError {}
+
 pub trait LLMApi: Copy {
     fn request_json_body(&self, prompt: String, output_length: u64) -> String;
     fn parse_response(response: Response) -> BTreeMap<String, String>;
 }
+
+/// A single blocking request/response round-trip against an LLM server. Unlike [`LLMApi`], the
+/// body is sized by token counts directly rather than a pre-rendered prompt string, since
+/// implementors (`DistserveProtocol`, `TgiProtocol`, ...) own their tokenizer.
+pub trait Protocol {
+    fn request_json_body(&self, input_token_length: u64, output_token_length: u64) -> String;
+
+    /// Parse a completed response into metrics. Implementors that also want body-derived fields
+    /// (e.g. a JSON `usage` object) need the body read, which `reqwest` only exposes as `async`,
+    /// so this is an `async fn` even for protocols (`DistserveProtocol`, `MockProtocol`) that never
+    /// actually await anything.
+    async fn parse_response(response: Response) -> BTreeMap<String, String>
+    where
+        Self: Sized;
+
+    /// Parse a response body asynchronously. `VllmProtocol` consumes a streaming (SSE) response and
+    /// measures timing metrics client-side instead of trusting server-reported `x-*` headers, which
+    /// a streaming response may not send; `TgiProtocol` reads a JSON `lags` array from the body.
+    /// Not every protocol overrides this, so it falls back to `unimplemented!` by default.
+    fn parse_response_async(response: Response) -> impl Future<Output = BTreeMap<String, String>>
+    where
+        Self: Sized,
+    {
+        let _ = response;
+        async { unimplemented!("{} does not support streaming responses", std::any::type_name::<Self>()) }
+    }
+
+    /// Coalesce `reqs.len()` (input, output) token-length pairs into a single request body the
+    /// server sees as one native batch, e.g. TGI's/vLLM's `MAX_CLIENT_BATCH_SIZE`-backed
+    /// `/v1/completions`. Unsupported by default; override alongside
+    /// [`parse_response_batched`](Protocol::parse_response_batched) on protocols whose server
+    /// accepts one.
+    fn request_json_body_batched(&self, reqs: &[(u64, u64)]) -> String {
+        let _ = reqs;
+        unimplemented!("{} does not support client-side batching", std::any::type_name::<Self>())
+    }
+
+    /// Split a [`request_json_body_batched`](Protocol::request_json_body_batched) response back
+    /// into one row per request that went into it, keyed by `choices[].index` so output rows line
+    /// up one-to-one with the requests that were coalesced into the batch.
+    fn parse_response_batched(
+        response: Response,
+        batch_size: usize,
+    ) -> impl Future<Output = Vec<BTreeMap<String, String>>>
+    where
+        Self: Sized,
+    {
+        let _ = (response, batch_size);
+        async { unimplemented!("{} does not support client-side batching", std::any::type_name::<Self>()) }
+    }
+}
+
+/// Why [`AsyncProtocol::send`] gave up without a response.
+#[derive(Debug)]
+pub enum SendError {
+    /// Every attempt hit a transport-level error (connect failure, timeout); carries the last one.
+    Transport(reqwest::Error),
+    /// Every attempt got back a non-retryable or exhausted-retry error status.
+    Status(StatusCode),
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::Transport(err) => write!(f, "transport error: {err}"),
+            SendError::Status(status) => write!(f, "server returned {status}"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+/// Retrying async counterpart to [`Protocol`], for drivers that want to ride out transient
+/// failures (connect errors, 5xx) instead of aborting the whole run on the first one.
+///
+/// A blanket impl covers every [`Protocol`] implementor for free: it re-renders the request body
+/// on each attempt (in case it embeds randomized token ids) and retries per `policy` on transport
+/// errors and 5xx responses.
+pub trait AsyncProtocol {
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        endpoint: &str,
+        input_len: u64,
+        output_len: u64,
+        policy: &RetryPolicy,
+    ) -> Result<BTreeMap<String, String>, SendError>;
+
+    /// Retrying counterpart to [`Protocol::request_json_body_batched`]/
+    /// [`Protocol::parse_response_batched`], built the same way [`AsyncProtocol::send`] is built on
+    /// [`Protocol::request_json_body`]/[`Protocol::parse_response`]. The whole batch retries as one
+    /// unit on a transport error or retryable status, same as a single-request [`send`](Self::send).
+    async fn send_batched(
+        &self,
+        client: &reqwest::Client,
+        endpoint: &str,
+        reqs: &[(u64, u64)],
+        policy: &RetryPolicy,
+    ) -> Result<Vec<BTreeMap<String, String>>, SendError>;
+}
+
+impl<P> AsyncProtocol for P
+where
+    P: Protocol + Sync,
+{
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        endpoint: &str,
+        input_len: u64,
+        output_len: u64,
+        policy: &RetryPolicy,
+    ) -> Result<BTreeMap<String, String>, SendError> {
+        let start = Instant::now();
+        let outcome = with_retry(
+            policy,
+            |response: &Response| {
+                let status = response.status();
+                !(status.is_server_error() || policy.is_retryable_status(status))
+            },
+            || {
+                let json_body = self.request_json_body(input_len, output_len);
+                let client = client.clone();
+                let endpoint = endpoint.to_string();
+                async move {
+                    client
+                        .post(&endpoint)
+                        .header("Content-Type", "application/json")
+                        .body(json_body)
+                        .send()
+                        .await
+                }
+            },
+        )
+        .await;
+
+        let mut map = match outcome.result {
+            Ok(response) if response.status().is_success() => P::parse_response(response).await,
+            Ok(response) => return Err(SendError::Status(response.status())),
+            Err(err) => return Err(SendError::Transport(err)),
+        };
+
+        map.insert("attempts".to_string(), (outcome.retry_count + 1).to_string());
+        map.insert(
+            "wall_time_ms".to_string(),
+            (start.elapsed().as_secs_f64() * 1000.0).to_string(),
+        );
+        Ok(map)
+    }
+
+    async fn send_batched(
+        &self,
+        client: &reqwest::Client,
+        endpoint: &str,
+        reqs: &[(u64, u64)],
+        policy: &RetryPolicy,
+    ) -> Result<Vec<BTreeMap<String, String>>, SendError> {
+        let start = Instant::now();
+        let outcome = with_retry(
+            policy,
+            |response: &Response| {
+                let status = response.status();
+                !(status.is_server_error() || policy.is_retryable_status(status))
+            },
+            || {
+                let json_body = self.request_json_body_batched(reqs);
+                let client = client.clone();
+                let endpoint = endpoint.to_string();
+                async move {
+                    client
+                        .post(&endpoint)
+                        .header("Content-Type", "application/json")
+                        .body(json_body)
+                        .send()
+                        .await
+                }
+            },
+        )
+        .await;
+
+        let mut rows = match outcome.result {
+            Ok(response) if response.status().is_success() => {
+                P::parse_response_batched(response, reqs.len()).await
+            }
+            Ok(response) => return Err(SendError::Status(response.status())),
+            Err(err) => return Err(SendError::Transport(err)),
+        };
+
+        let attempts = (outcome.retry_count + 1).to_string();
+        let wall_time_ms = (start.elapsed().as_secs_f64() * 1000.0).to_string();
+        for row in &mut rows {
+            row.insert("attempts".to_string(), attempts.clone());
+            row.insert("wall_time_ms".to_string(), wall_time_ms.clone());
+        }
+        Ok(rows)
+    }
+}
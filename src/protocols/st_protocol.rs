@@ -1,8 +1,12 @@
 use std::collections::BTreeMap;
+use std::time::Instant;
 
+use futures_util::StreamExt;
 use reqwest::Response;
 use tokenizers::Tokenizer;
 
+use crate::percentile;
+
 use super::Protocol;
 
 pub struct StProtocol {
@@ -31,8 +35,8 @@ impl Protocol for StProtocol {
         json_body.to_string()
     }
 
-    fn parse_response(&self) -> fn(response: Response) -> BTreeMap<String, String> {
-        |response: Response| -> BTreeMap<String, String> {
+    async fn parse_response(response: Response) -> BTreeMap<String, String> {
+        {
             let mut map = BTreeMap::new();
             map.insert("status".to_string(), response.status().as_str().to_string());
             if response.status().is_success() {
@@ -152,6 +156,99 @@ impl Protocol for StProtocol {
     }
 }
 
+impl StProtocol {
+    /// Same request body as [`Protocol::request_json_body`], but with `"stream": true` so the
+    /// server emits the response as Server-Sent-Events instead of buffering it behind headers.
+    pub fn request_json_body_streaming(
+        &self,
+        input_token_length: u64,
+        output_token_length: u64,
+    ) -> String {
+        let input = vec![self.target_token; input_token_length as usize];
+        let input = self.tokenizer.decode(&input, false).unwrap();
+        let json_body = serde_json::json!({
+            "inputs": input,
+            "parameters": {"max_new_tokens": output_token_length},
+            "stream": true,
+        });
+        json_body.to_string()
+    }
+
+    /// Consume a streaming response and measure TTFT / inter-token latency client-side, so the
+    /// metrics no longer depend on the `x-*-time` headers this server happens to emit.
+    ///
+    /// Every non-empty `data: ` SSE chunk is treated as one emitted token. The wall-clock delta
+    /// between the request being sent and the first chunk is the TTFT; the deltas between every
+    /// following pair of chunks feed the inter-token-gap percentiles.
+    pub async fn parse_response_streaming(response: Response) -> BTreeMap<String, String> {
+        let mut map = BTreeMap::new();
+        map.insert("status".to_string(), response.status().as_str().to_string());
+        if !response.status().is_success() {
+            return map;
+        }
+
+        let start = Instant::now();
+        let mut first_token_time = None;
+        let mut last_token_time = start;
+        let mut gaps = Vec::new();
+        let mut output_length = 0u64;
+
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(_) => break,
+            };
+            for line in chunk.split(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(line);
+                let data = line.trim_start_matches("data:").trim();
+                if data.is_empty() || data == "[DONE]" {
+                    continue;
+                }
+
+                let now = Instant::now();
+                match first_token_time {
+                    None => first_token_time = Some(now.duration_since(start)),
+                    Some(_) => gaps.push(now.duration_since(last_token_time).as_secs_f64()),
+                }
+                last_token_time = now;
+                output_length += 1;
+            }
+        }
+
+        let total_time = last_token_time.duration_since(start).as_secs_f64();
+        map.insert(
+            "first_token_time".to_string(),
+            first_token_time.unwrap_or_default().as_secs_f64().to_string(),
+        );
+        map.insert("total_time".to_string(), total_time.to_string());
+        map.insert("output_length".to_string(), output_length.to_string());
+
+        if !gaps.is_empty() {
+            let mut sorted_gaps = gaps.clone();
+            sorted_gaps.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let avg = gaps.iter().sum::<f64>() / gaps.len() as f64;
+            map.insert("min_time_between_tokens".to_string(), sorted_gaps[0].to_string());
+            map.insert("avg_time_between_tokens".to_string(), avg.to_string());
+            map.insert(
+                "p90_time_between_tokens".to_string(),
+                percentile(&sorted_gaps, 0.90).to_string(),
+            );
+            map.insert(
+                "p95_time_between_tokens".to_string(),
+                percentile(&sorted_gaps, 0.95).to_string(),
+            );
+            map.insert(
+                "p99_time_between_tokens".to_string(),
+                percentile(&sorted_gaps, 0.99).to_string(),
+            );
+        }
+
+        map
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
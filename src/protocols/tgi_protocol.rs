@@ -1,10 +1,37 @@
-use std::{collections::BTreeMap, future::Future};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    path::Path,
+    sync::OnceLock,
+    time::Instant,
+};
 
+use futures_util::StreamExt;
 use rand::{thread_rng, Rng};
 use reqwest::Response;
 use tokenizers::Tokenizer;
 
-use super::Protocol;
+use crate::percentile;
+use crate::response_schema::{Conversion, ResponseSchema};
+
+use super::{GrammarSpec, Protocol};
+
+/// Schema for the `x-*` timing headers a non-streaming TGI response reports.
+fn response_schema() -> &'static ResponseSchema {
+    static SCHEMA: OnceLock<ResponseSchema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        ResponseSchema::new()
+            .field("first_token_time", "x-first-token-time", Conversion::Float)
+            .field("total_time", "x-total-time", Conversion::Float)
+            .field("inference_time", "x-inference-time", Conversion::Float)
+            .field("queue_time", "x-queue-time", Conversion::Float)
+            .field(
+                "max_time_between_tokens",
+                "x-max-time-between-tokens",
+                Conversion::Float,
+            )
+    })
+}
 
 pub struct TgiProtocol {
     tokenizer: Tokenizer,
@@ -14,89 +41,170 @@ pub struct TgiProtocol {
 
     /// End of the token id range.
     end: u32,
+
+    /// Special-token ids (BOS/EOS/PAD/...) rejected during sampling so a generated prompt never
+    /// decodes through one of them, which would skew the measured `input_token_length`.
+    exclude: HashSet<u32>,
+
+    /// Constrained-decoding grammar injected into the request body, if any.
+    grammar: Option<GrammarSpec>,
 }
 
 impl TgiProtocol {
-    /// Current the randomly generated token ids are in the range of 0..10000.
+    /// Token ids are sampled from `0..tokenizer.get_vocab_size(false)`, with no special-token
+    /// exclusion. Prefer [`Self::new_with_vocab_bounds`] for prompts that must decode cleanly.
     pub fn new(tokenizer: Tokenizer) -> Self {
+        let end = tokenizer.get_vocab_size(false) as u32;
+        Self {
+            tokenizer,
+            start: 0,
+            end,
+            exclude: HashSet::new(),
+            grammar: None,
+        }
+    }
+
+    /// Loads the tokenizer from `tokenizer_path` and, when `exclude_special` is set, also loads
+    /// `special_token_map.json` from the same directory and excludes those ids from sampling.
+    ///
+    /// TGI's `input` field is always clean decoded text, so `exclude_special` should normally stay
+    /// `true` here: a stray BOS/EOS/PAD in the sampled ids can decode to empty or invalid text and
+    /// understate the real input length.
+    pub fn new_with_vocab_bounds(tokenizer_path: impl AsRef<Path>, exclude_special: bool) -> Self {
+        let tokenizer_path = tokenizer_path.as_ref();
+        let tokenizer = Tokenizer::from_file(tokenizer_path).expect("failed to load tokenizer");
+        let end = tokenizer.get_vocab_size(false) as u32;
+        let exclude = if exclude_special {
+            load_special_token_ids(tokenizer_path)
+        } else {
+            HashSet::new()
+        };
         Self {
             tokenizer,
             start: 0,
-            end: 10000,
+            end,
+            exclude,
+            grammar: None,
+        }
+    }
+
+    /// Attach a constrained-decoding grammar, validating it up front so a malformed
+    /// [`GrammarSpec::Json`] schema fails here instead of as an opaque server 422.
+    pub fn with_grammar(mut self, grammar: GrammarSpec) -> Result<Self, super::GrammarError> {
+        grammar.validate()?;
+        self.grammar = Some(grammar);
+        Ok(self)
+    }
+
+    /// Sample a single token id from `start..end`, rejecting ids in `exclude`.
+    fn sample_token_id(&self) -> u32 {
+        loop {
+            let id = thread_rng().gen_range(self.start..self.end);
+            if !self.exclude.contains(&id) {
+                return id;
+            }
         }
     }
 }
 
+/// Token ids to exclude from sampling, loaded from a `special_token_map.json` living alongside
+/// `tokenizer_path` (maps a special-token name, e.g. `"eos_token_id"`, to its id). Missing or
+/// unparseable files are treated as "no special tokens to exclude" rather than an error, since not
+/// every tokenizer directory ships one.
+fn load_special_token_ids(tokenizer_path: &Path) -> HashSet<u32> {
+    let Some(dir) = tokenizer_path.parent() else {
+        return HashSet::new();
+    };
+    let Ok(contents) = std::fs::read_to_string(dir.join("special_token_map.json")) else {
+        return HashSet::new();
+    };
+    serde_json::from_str::<HashMap<String, u32>>(&contents)
+        .map(|map| map.into_values().collect())
+        .unwrap_or_default()
+}
+
 #[derive(serde::Deserialize, Debug)]
 pub struct TgiParsed {
     pub lags: Vec<f64>,
 }
 
+/// `usage` accounting and per-choice `finish_reason` from a non-streaming completion body.
+#[derive(serde::Deserialize, Debug)]
+struct UsageInfo {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct CompletionChoice {
+    #[serde(default)]
+    finish_reason: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct CompletionBody {
+    #[serde(default)]
+    choices: Vec<CompletionChoice>,
+    usage: Option<UsageInfo>,
+}
+
 impl Protocol for TgiProtocol {
     fn request_json_body(&self, input_token_length: u64, output_token_length: u64) -> String {
         let input_token_ids = (0..input_token_length)
-            .map(|_| thread_rng().gen_range(self.start..self.end))
+            .map(|_| self.sample_token_id())
             .collect::<Vec<_>>();
         let input = self
             .tokenizer
             .decode(input_token_ids.as_slice(), false)
             .unwrap();
-        let json_body =
+        let mut json_body =
             serde_json::json!({"input":input,"parameter":{"max_new_tokens":output_token_length}});
+        if let Some(grammar) = &self.grammar {
+            json_body["grammar"] = match grammar {
+                GrammarSpec::Json(schema) => serde_json::json!({"type": "json", "value": schema}),
+                GrammarSpec::Regex(pattern) => serde_json::json!({"type": "regex", "value": pattern}),
+            };
+        }
         json_body.to_string()
     }
 
-    fn parse_response(response: Response) -> BTreeMap<String, String> {
+    async fn parse_response(response: Response) -> BTreeMap<String, String> {
         let mut map = BTreeMap::new();
         map.insert("status".to_string(), response.status().as_str().to_string());
-        if response.status().is_success() {
-            let first_token_time = response
-                .headers()
-                .get("x-first-token-time")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            map.insert("first_token_time".to_string(), first_token_time);
-
-            let total_time = response
-                .headers()
-                .get("x-total-time")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            map.insert("total_time".to_string(), total_time);
-
-            let inference_time = response
-                .headers()
-                .get("x-inference-time")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            map.insert("inference_time".to_string(), inference_time);
-
-            let queue_time = response
-                .headers()
-                .get("x-queue-time")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            map.insert("queue_time".to_string(), queue_time);
+        if !response.status().is_success() {
+            return map;
+        }
+        response_schema().apply(response.headers(), &mut map);
 
-            let max_time_between_tokens = response
-                .headers()
-                .get("x-max-time-between-tokens")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
+        let Ok(body) = response.text().await else {
+            return map;
+        };
+        let Ok(completion) = serde_json::from_str::<CompletionBody>(&body) else {
+            return map;
+        };
+        if let Some(choice) = completion.choices.first() {
+            map.insert("finish_reason".to_string(), choice.finish_reason.clone());
+        }
+        if let Some(usage) = completion.usage {
+            map.insert("prompt_tokens".to_string(), usage.prompt_tokens.to_string());
             map.insert(
-                "max_time_between_tokens".to_string(),
-                max_time_between_tokens,
+                "completion_tokens".to_string(),
+                usage.completion_tokens.to_string(),
             );
+
+            // `output_token_length` is what we asked for; servers often stop early on `eos_token`, so
+            // `completion_tokens` is what actually happened and is what throughput should be based on.
+            let inference_time = map
+                .get("inference_time")
+                .and_then(|value| value.parse::<f64>().ok());
+            if let Some(inference_time) = inference_time.filter(|t| *t > 0.0) {
+                map.insert(
+                    "output_tokens_per_second".to_string(),
+                    (usage.completion_tokens as f64 / inference_time).to_string(),
+                );
+            }
         }
         map
     }
@@ -117,8 +225,193 @@ impl Protocol for TgiProtocol {
             map
         }
     }
+
+    /// Coalesce `reqs.len()` dataset entries into a single request whose `input` field is a JSON
+    /// array of decoded prompts, so the server sees one native batch (TGI's
+    /// `MAX_CLIENT_BATCH_SIZE`) instead of `reqs.len()` separate HTTP requests. `max_new_tokens` is
+    /// shared across the batch, so it is sized to the longest requested output in it.
+    fn request_json_body_batched(&self, reqs: &[(u64, u64)]) -> String {
+        let inputs = reqs
+            .iter()
+            .map(|&(input_token_length, _)| {
+                let input_token_ids = (0..input_token_length)
+                    .map(|_| self.sample_token_id())
+                    .collect::<Vec<_>>();
+                self.tokenizer
+                    .decode(input_token_ids.as_slice(), false)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let max_new_tokens = reqs
+            .iter()
+            .map(|&(_, output_token_length)| output_token_length)
+            .max()
+            .unwrap_or(0);
+        let json_body = serde_json::json!({
+            "input": inputs,
+            "parameter": {"max_new_tokens": max_new_tokens},
+        });
+        json_body.to_string()
+    }
+
+    /// Split a [`request_json_body_batched`](Protocol::request_json_body_batched) response back
+    /// into one row per request that went into it, keyed by `choices[].index` so output rows
+    /// still line up one-to-one with the requests that were coalesced into the batch.
+    fn parse_response_batched(
+        response: Response,
+        batch_size: usize,
+    ) -> impl Future<Output = Vec<BTreeMap<String, String>>> {
+        async move {
+            let status = response.status();
+            let mut rows = (0..batch_size)
+                .map(|_| {
+                    let mut map = BTreeMap::new();
+                    map.insert("status".to_string(), status.as_str().to_string());
+                    map
+                })
+                .collect::<Vec<_>>();
+
+            if !status.is_success() {
+                return rows;
+            }
+
+            let Ok(batch_response) = response.json::<BatchResponse>().await else {
+                return rows;
+            };
+            for choice in batch_response.choices {
+                if let Some(row) = rows.get_mut(choice.index) {
+                    row.insert("generated_text".to_string(), choice.text);
+                }
+            }
+            rows
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct StreamChoice {
+    text: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
 }
 
+impl TgiProtocol {
+    /// Same request body as [`Protocol::request_json_body`], but with `"stream": true` so the
+    /// server emits `text/event-stream` chunks instead of the `x-*-time` headers
+    /// [`Protocol::parse_response`] relies on.
+    pub fn request_json_body_streaming(
+        &self,
+        input_token_length: u64,
+        output_token_length: u64,
+    ) -> String {
+        let input_token_ids = (0..input_token_length)
+            .map(|_| self.sample_token_id())
+            .collect::<Vec<_>>();
+        let input = self
+            .tokenizer
+            .decode(input_token_ids.as_slice(), false)
+            .unwrap();
+        let json_body = serde_json::json!({
+            "input": input,
+            "parameter": {"max_new_tokens": output_token_length},
+            "stream": true,
+        });
+        json_body.to_string()
+    }
+
+    /// Consume a streaming response and measure TTFT / inter-token latency client-side instead
+    /// of trusting the server's `x-*-time` headers, which not every endpoint sends.
+    ///
+    /// Every SSE chunk with a non-empty `choices[].text` fragment counts as one generated token.
+    /// Reports `ttft_ms` (time to the first such chunk), `p50_inter_token_ms`/`p99_inter_token_ms`
+    /// (percentiles of the gaps between subsequent chunks), and `generated_tokens`.
+    pub async fn parse_response_streaming(response: Response) -> BTreeMap<String, String> {
+        let mut map = BTreeMap::new();
+        map.insert("status".to_string(), response.status().as_str().to_string());
+        if !response.status().is_success() {
+            return map;
+        }
+
+        let start = Instant::now();
+        let mut ttft = None;
+        let mut last_token_time = start;
+        let mut gaps = Vec::new();
+        let mut generated_tokens = 0u64;
+
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(_) => break,
+            };
+            for line in chunk.split(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(line);
+                let data = line.trim_start_matches("data:").trim();
+                if data.is_empty() || data == "[DONE]" {
+                    continue;
+                }
+
+                let has_text = serde_json::from_str::<StreamChunk>(data)
+                    .ok()
+                    .map(|chunk| chunk.choices.iter().any(|choice| !choice.text.is_empty()))
+                    .unwrap_or(false);
+                if !has_text {
+                    continue;
+                }
+
+                let now = Instant::now();
+                match ttft {
+                    None => ttft = Some(now.duration_since(start)),
+                    Some(_) => {
+                        gaps.push(now.duration_since(last_token_time).as_secs_f64() * 1000.0)
+                    }
+                }
+                last_token_time = now;
+                generated_tokens += 1;
+            }
+        }
+
+        map.insert(
+            "ttft_ms".to_string(),
+            (ttft.unwrap_or_default().as_secs_f64() * 1000.0).to_string(),
+        );
+        map.insert("generated_tokens".to_string(), generated_tokens.to_string());
+
+        if !gaps.is_empty() {
+            let mut sorted_gaps = gaps.clone();
+            sorted_gaps.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            map.insert(
+                "p50_inter_token_ms".to_string(),
+                percentile(&sorted_gaps, 0.50).to_string(),
+            );
+            map.insert(
+                "p99_inter_token_ms".to_string(),
+                percentile(&sorted_gaps, 0.99).to_string(),
+            );
+        }
+
+        map
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct BatchChoice {
+    index: usize,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct BatchResponse {
+    #[serde(default)]
+    choices: Vec<BatchChoice>,
+}
+
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
@@ -166,4 +459,22 @@ mod tests {
         let parsed = TgiProtocol::parse_response_async(response).await;
         println!("{:?}", parsed);
     }
+
+    #[tokio::test]
+    async fn test_parse_response_batched_splits_rows_by_choice_index() {
+        let body = json!({"choices": [
+            {"index": 1, "text": "second"},
+            {"index": 0, "text": "first"},
+        ]})
+        .to_string();
+        let response = reqwest::Response::from(
+            http::response::Builder::new().status(200).body(body).unwrap(),
+        );
+
+        let rows = TgiProtocol::parse_response_batched(response, 2).await;
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get("generated_text"), Some(&"first".to_string()));
+        assert_eq!(rows[1].get("generated_text"), Some(&"second".to_string()));
+    }
 }
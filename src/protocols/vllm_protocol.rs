@@ -1,10 +1,86 @@
-use std::{collections::BTreeMap, future::Future};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    path::Path,
+    time::Instant,
+};
 
+use futures_util::StreamExt;
 use rand::{thread_rng, Rng};
 use reqwest::Response;
 use tokenizers::Tokenizer;
 
-use super::Protocol;
+use crate::percentile;
+
+use super::{GrammarSpec, Protocol};
+
+#[derive(serde::Deserialize, Debug)]
+struct StreamChoice {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    delta: Option<StreamDelta>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct StreamDelta {
+    #[serde(default)]
+    content: String,
+}
+
+impl StreamChoice {
+    /// Either OpenAI-style `choices[].text` or chat-style `choices[].delta.content` counts as one
+    /// generated token fragment.
+    fn token_text(&self) -> &str {
+        if !self.text.is_empty() {
+            &self.text
+        } else {
+            self.delta.as_ref().map(|delta| delta.content.as_str()).unwrap_or("")
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct StreamEvent {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct BatchChoice {
+    index: usize,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct BatchResponse {
+    #[serde(default)]
+    choices: Vec<BatchChoice>,
+}
+
+/// `usage` accounting and per-choice `finish_reason` from a non-streaming completion body.
+#[derive(serde::Deserialize, Debug)]
+struct UsageInfo {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct CompletionChoice {
+    #[serde(default)]
+    finish_reason: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct CompletionBody {
+    #[serde(default)]
+    choices: Vec<CompletionChoice>,
+    usage: Option<UsageInfo>,
+}
+
 
 pub struct VllmProtocol {
     tokenizer: Tokenizer,
@@ -14,72 +90,319 @@ pub struct VllmProtocol {
 
     /// End of the token id range.
     end: u32,
+
+    /// Special-token ids (BOS/EOS/PAD/...) rejected during sampling so a generated prompt never
+    /// decodes through one of them, which would skew the measured `input_token_length`.
+    exclude: HashSet<u32>,
+
+    /// Constrained-decoding grammar injected into the request body, if any.
+    grammar: Option<GrammarSpec>,
 }
 
 impl VllmProtocol {
-    /// Current the randomly generated token ids are in the range of 0..10000.
+    /// Token ids are sampled from `0..tokenizer.get_vocab_size(false)`, with no special-token
+    /// exclusion. Prefer [`Self::new_with_vocab_bounds`] when the prompt needs to decode cleanly.
     pub fn new(tokenizer: Tokenizer) -> Self {
+        let end = tokenizer.get_vocab_size(false) as u32;
+        Self {
+            tokenizer,
+            start: 0,
+            end,
+            exclude: HashSet::new(),
+            grammar: None,
+        }
+    }
+
+    /// Loads the tokenizer from `tokenizer_path` and, when `exclude_special` is set, also loads
+    /// `special_token_map.json` from the same directory and excludes those ids from sampling.
+    ///
+    /// `exclude_special` should stay `false` for `tokens`-style requests that vLLM accepts
+    /// directly (special ids are a legitimate part of the protocol under test there), and `true`
+    /// when the sampled ids are decoded to text first, since a stray BOS/EOS/PAD can decode to
+    /// empty or invalid text and understate the real input length.
+    pub fn new_with_vocab_bounds(tokenizer_path: impl AsRef<Path>, exclude_special: bool) -> Self {
+        let tokenizer_path = tokenizer_path.as_ref();
+        let tokenizer = Tokenizer::from_file(tokenizer_path).expect("failed to load tokenizer");
+        let end = tokenizer.get_vocab_size(false) as u32;
+        let exclude = if exclude_special {
+            load_special_token_ids(tokenizer_path)
+        } else {
+            HashSet::new()
+        };
         Self {
             tokenizer,
             start: 0,
-            end: 10000,
+            end,
+            exclude,
+            grammar: None,
+        }
+    }
+
+    /// Attach a constrained-decoding grammar, validating it up front so a malformed
+    /// [`GrammarSpec::Json`] schema fails here instead of as an opaque server 422.
+    pub fn with_grammar(mut self, grammar: GrammarSpec) -> Result<Self, super::GrammarError> {
+        grammar.validate()?;
+        self.grammar = Some(grammar);
+        Ok(self)
+    }
+
+    /// Sample a single token id from `start..end`, rejecting ids in `exclude`.
+    fn sample_token_id(&self) -> u32 {
+        loop {
+            let id = thread_rng().gen_range(self.start..self.end);
+            if !self.exclude.contains(&id) {
+                return id;
+            }
         }
     }
 }
 
+/// Token ids to exclude from sampling, loaded from a `special_token_map.json` living alongside
+/// `tokenizer_path` (maps a special-token name, e.g. `"eos_token_id"`, to its id). Missing or
+/// unparseable files are treated as "no special tokens to exclude" rather than an error, since not
+/// every tokenizer directory ships one.
+fn load_special_token_ids(tokenizer_path: &Path) -> HashSet<u32> {
+    let Some(dir) = tokenizer_path.parent() else {
+        return HashSet::new();
+    };
+    let Ok(contents) = std::fs::read_to_string(dir.join("special_token_map.json")) else {
+        return HashSet::new();
+    };
+    serde_json::from_str::<HashMap<String, u32>>(&contents)
+        .map(|map| map.into_values().collect())
+        .unwrap_or_default()
+}
+
 impl Protocol for VllmProtocol {
     fn request_json_body(&self, input_token_length: u64, output_token_length: u64) -> String {
         let input_token_ids = (0..input_token_length)
-            .map(|_| thread_rng().gen_range(self.start..self.end))
+            .map(|_| self.sample_token_id())
             .collect::<Vec<_>>();
         let _input = self
             .tokenizer
             .decode(input_token_ids.as_slice(), false)
             .unwrap();
-        let json_body =
+        let mut json_body =
             serde_json::json!({"max_tokens": output_token_length, "tokens": input_token_ids});
+        if let Some(grammar) = &self.grammar {
+            match grammar {
+                GrammarSpec::Json(schema) => json_body["guided_json"] = schema.clone(),
+                GrammarSpec::Regex(pattern) => json_body["guided_regex"] = serde_json::json!(pattern),
+            }
+        }
         json_body.to_string()
     }
 
-    fn parse_response(response: Response, _input_token_length:Option<u64>) -> BTreeMap<String, String> {
+    async fn parse_response(response: Response) -> BTreeMap<String, String> {
         let mut map = BTreeMap::new();
         map.insert("status".to_string(), response.status().as_str().to_string());
-        if response.status().is_success() {
-            let first_token_time = response
-                .headers()
-                .get("x-first-token-time")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            map.insert("first_token_time".to_string(), first_token_time);
+        if !response.status().is_success() {
+            return map;
+        }
 
-            let inference_time = response
-                .headers()
-                .get("x-inference-time")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            map.insert("inference_time".to_string(), inference_time);
+        let first_token_time = response
+            .headers()
+            .get("x-first-token-time")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        map.insert("first_token_time".to_string(), first_token_time);
 
-            let max_time_between_tokens = response
-                .headers()
-                .get("x-max-time-between-tokens")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
+        let inference_time = response
+            .headers()
+            .get("x-inference-time")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        map.insert("inference_time".to_string(), inference_time.clone());
+
+        let max_time_between_tokens = response
+            .headers()
+            .get("x-max-time-between-tokens")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        map.insert(
+            "max_time_between_tokens".to_string(),
+            max_time_between_tokens,
+        );
+
+        let Ok(body) = response.text().await else {
+            return map;
+        };
+        let Ok(completion) = serde_json::from_str::<CompletionBody>(&body) else {
+            return map;
+        };
+        if let Some(choice) = completion.choices.first() {
+            map.insert("finish_reason".to_string(), choice.finish_reason.clone());
+        }
+        if let Some(usage) = completion.usage {
+            map.insert("prompt_tokens".to_string(), usage.prompt_tokens.to_string());
             map.insert(
-                "max_time_between_tokens".to_string(),
-                max_time_between_tokens,
+                "completion_tokens".to_string(),
+                usage.completion_tokens.to_string(),
             );
+
+            // `output_token_length` is what we asked for; servers often stop early on `eos_token`, so
+            // `completion_tokens` is what actually happened and is what throughput should be based on.
+            if let Ok(inference_time) = inference_time.parse::<f64>() {
+                if inference_time > 0.0 {
+                    map.insert(
+                        "output_tokens_per_second".to_string(),
+                        (usage.completion_tokens as f64 / inference_time).to_string(),
+                    );
+                }
+            }
         }
         map
     }
 
-    fn parse_response_async(_: Response) -> impl Future<Output = BTreeMap<String, String>> {
-        async { unimplemented!() }
+    /// Consume an OpenAI-style `text/event-stream` completion response and measure TTFT /
+    /// inter-token latency client-side instead of trusting `x-*-time` headers, which not every
+    /// server sends on a streaming response.
+    ///
+    /// Events are separated by a blank line (`\n\n`); a `data:` line's remainder is the JSON
+    /// payload, terminated by the `[DONE]` sentinel. A chunk boundary may land inside an event, so
+    /// bytes are accumulated into `buffer` and only the text up to the last `\n\n` is drained on
+    /// each poll, leaving a trailing partial event for the next chunk.
+    fn parse_response_async(response: Response) -> impl Future<Output = BTreeMap<String, String>> {
+        async move {
+            let mut map = BTreeMap::new();
+            map.insert("status".to_string(), response.status().as_str().to_string());
+            if !response.status().is_success() {
+                return map;
+            }
+
+            let start = Instant::now();
+            let mut first_token_time = None;
+            let mut last_token_time = start;
+            let mut gaps = Vec::new();
+            let mut total_tokens = 0u64;
+            let mut buffer = String::new();
+
+            let mut stream = response.bytes_stream();
+            'outer: while let Some(chunk) = stream.next().await {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(_) => break,
+                };
+                buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+                while let Some(pos) = buffer.find("\n\n") {
+                    let event = buffer[..pos].to_string();
+                    buffer.drain(..pos + 2);
+
+                    for line in event.lines() {
+                        let data = line.trim_start_matches("data:").trim();
+                        if data.is_empty() {
+                            continue;
+                        }
+                        if data == "[DONE]" {
+                            break 'outer;
+                        }
+
+                        let Ok(event) = serde_json::from_str::<StreamEvent>(data) else {
+                            continue;
+                        };
+                        let has_text = event.choices.iter().any(|choice| !choice.token_text().is_empty());
+                        if !has_text {
+                            continue;
+                        }
+
+                        let now = Instant::now();
+                        match first_token_time {
+                            None => first_token_time = Some(now.duration_since(start)),
+                            Some(_) => {
+                                gaps.push(now.duration_since(last_token_time).as_secs_f64() * 1000.0)
+                            }
+                        }
+                        last_token_time = now;
+                        total_tokens += 1;
+                    }
+                }
+            }
+
+            map.insert(
+                "first_token_time".to_string(),
+                first_token_time.unwrap_or_default().as_secs_f64().to_string(),
+            );
+            map.insert("total_tokens".to_string(), total_tokens.to_string());
+
+            if !gaps.is_empty() {
+                let mut sorted_gaps = gaps.clone();
+                sorted_gaps.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                map.insert(
+                    "max_time_between_tokens".to_string(),
+                    sorted_gaps.last().copied().unwrap_or(0.0).to_string(),
+                );
+                map.insert(
+                    "inter_token_latency_p50".to_string(),
+                    percentile(&sorted_gaps, 0.50).to_string(),
+                );
+                map.insert(
+                    "inter_token_latency_p99".to_string(),
+                    percentile(&sorted_gaps, 0.99).to_string(),
+                );
+            }
+
+            map
+        }
+    }
+
+    /// Coalesce `reqs.len()` (input, output) token-length pairs into a single request whose
+    /// `tokens` field is an array of per-request token-id arrays, so the server sees one native
+    /// batch instead of `reqs.len()` separate HTTP requests.
+    fn request_json_body_batched(&self, reqs: &[(u64, u64)]) -> String {
+        let tokens = reqs
+            .iter()
+            .map(|&(input_token_length, _)| {
+                (0..input_token_length)
+                    .map(|_| self.sample_token_id())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let max_tokens = reqs
+            .iter()
+            .map(|&(_, output_token_length)| output_token_length)
+            .collect::<Vec<_>>();
+        let json_body = serde_json::json!({"tokens": tokens, "max_tokens": max_tokens});
+        json_body.to_string()
+    }
+
+    /// Split a [`request_json_body_batched`](Protocol::request_json_body_batched) response back
+    /// into one row per request that went into it, keyed by `choices[].index` so output rows line
+    /// up one-to-one with the requests that were coalesced into the batch.
+    fn parse_response_batched(
+        response: Response,
+        batch_size: usize,
+    ) -> impl Future<Output = Vec<BTreeMap<String, String>>> {
+        async move {
+            let status = response.status();
+            let mut rows = (0..batch_size)
+                .map(|_| {
+                    let mut map = BTreeMap::new();
+                    map.insert("status".to_string(), status.as_str().to_string());
+                    map
+                })
+                .collect::<Vec<_>>();
+
+            if !status.is_success() {
+                return rows;
+            }
+
+            let Ok(batch_response) = response.json::<BatchResponse>().await else {
+                return rows;
+            };
+            for choice in batch_response.choices {
+                if let Some(row) = rows.get_mut(choice.index) {
+                    row.insert("generated_text".to_string(), choice.text);
+                }
+            }
+            rows
+        }
     }
 }
 
@@ -116,4 +439,40 @@ mod tests {
             print!("Tokenizer file not found");
         }
     }
+
+    #[tokio::test]
+    async fn test_parse_response_async_streams_sse_events() {
+        let body = concat!(
+            "data: {\"choices\":[{\"text\":\"Hello\"}]}\n\n",
+            "data: {\"choices\":[{\"text\":\" world\"}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let response = reqwest::Response::from(
+            http::response::Builder::new()
+                .status(200)
+                .body(body.to_string())
+                .unwrap(),
+        );
+        let parsed = VllmProtocol::parse_response_async(response).await;
+        assert_eq!(parsed.get("total_tokens"), Some(&"2".to_string()));
+        assert!(parsed.contains_key("first_token_time"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_response_batched_splits_rows_by_choice_index() {
+        let body = serde_json::json!({"choices": [
+            {"index": 1, "text": "second"},
+            {"index": 0, "text": "first"},
+        ]})
+        .to_string();
+        let response = reqwest::Response::from(
+            http::response::Builder::new().status(200).body(body).unwrap(),
+        );
+
+        let rows = VllmProtocol::parse_response_batched(response, 2).await;
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get("generated_text"), Some(&"first".to_string()));
+        assert_eq!(rows[1].get("generated_text"), Some(&"second".to_string()));
+    }
 }
@@ -0,0 +1,119 @@
+//! Token-bucket client-side rate limiter for the request loops in [`crate::requester`].
+//!
+//! [`RetryPolicy`](crate::retry::RetryPolicy) keeps transient server failures from polluting
+//! latency results; this keeps the generator itself from overshooting a server's admission limit
+//! in the first place, by gating dispatch on a token bucket instead of just the pacing interval.
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Target rate plus how much burst above it to allow, and a safety margin to stay under a hard
+/// server-side limit.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimiterConfig {
+    /// Target requests per second.
+    pub rate: f64,
+    /// Fraction of one second's token budget the bucket may accumulate as burst capacity, e.g.
+    /// `0.2` allows bursting 20% above the steady drip rate.
+    pub burst_pct: f64,
+    /// Extra duration folded into every refill interval, so the effective rate sits safely under
+    /// `rate` instead of riding right up against a hard limit.
+    pub duration_overhead: Duration,
+}
+
+struct State {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// A token bucket shared by every request task spawned from one `spawn_request_loop*`
+/// invocation. Call [`acquire`](RateLimiter::acquire) before dispatching a request.
+pub struct RateLimiter {
+    config: RateLimiterConfig,
+    state: Mutex<State>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimiterConfig) -> Self {
+        let limiter = Self {
+            config,
+            state: Mutex::new(State {
+                tokens: 0.0,
+                last_refill: Instant::now(),
+            }),
+        };
+        limiter.state.lock().unwrap().tokens = limiter.capacity();
+        limiter
+    }
+
+    /// Interval between individual token refills, including [`RateLimiterConfig::duration_overhead`].
+    fn refill_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.config.rate) + self.config.duration_overhead
+    }
+
+    /// Effective rate once `duration_overhead` has been folded in; always <= `config.rate`.
+    fn effective_rate(&self) -> f64 {
+        1.0 / self.refill_interval().as_secs_f64()
+    }
+
+    fn capacity(&self) -> f64 {
+        self.effective_rate() * (1.0 + self.config.burst_pct)
+    }
+
+    /// Block until one token is available, refilling the bucket from elapsed wall-clock time.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+                let refilled =
+                    now.duration_since(state.last_refill).as_secs_f64() * self.effective_rate();
+                if refilled > 0.0 {
+                    state.tokens = (state.tokens + refilled).min(self.capacity());
+                    state.last_refill = now;
+                }
+
+                if state.tokens >= 1.0 {
+                    state.tokens -= 1.0;
+                    None
+                } else {
+                    Some(Duration::from_secs_f64(
+                        (1.0 - state.tokens) / self.effective_rate(),
+                    ))
+                }
+            };
+
+            match wait {
+                None => return,
+                Some(duration) => tokio::time::sleep(duration).await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn bursts_up_to_capacity_then_throttles() {
+        let limiter = RateLimiter::new(RateLimiterConfig {
+            rate: 100.0,
+            burst_pct: 0.5,
+            duration_overhead: Duration::ZERO,
+        });
+
+        let capacity = limiter.capacity().floor() as usize;
+        let start = Instant::now();
+        for _ in 0..capacity {
+            limiter.acquire().await;
+        }
+        // The initial burst should drain near-instantly.
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        // The next acquire has to wait for a refill.
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(5));
+    }
+}
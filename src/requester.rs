@@ -19,7 +19,14 @@ use tokio::{
 };
 
 use crate::{
-    apis::LLMApi, dataset::LLMTrace, distribution::Distribution,
+    adaptive::AdaptiveRateController,
+    apis::{client::SyncSendOutcome, HttpLLMClient, LLMApi, SyncLLMClient},
+    auth::EndpointAuth,
+    dataset::LLMTrace,
+    dispatch::{DispatchJob, Dispatcher},
+    distribution::{gamma::Gamma, Distribution},
+    rate_limiter::RateLimiter,
+    retry::RetryPolicy,
     token_sampler::TokenSampler,
 };
 
@@ -48,38 +55,89 @@ pub fn create_gamma_interval_generator(request_rate: f64, cv: f64) -> IntervalGe
     IntervalGenerator::new(distribution)
 }
 
-#[allow(dead_code)]
-async fn request(endpoint: &str, json_body: String) -> Result<Response, reqwest::Error> {
-    Ok(reqwest::Client::builder()
+/// Build the single [`reqwest::Client`] shared by every request task spawned by a
+/// `spawn_request_loop*` invocation.
+///
+/// Reusing one client keeps the underlying connection pool warm across requests instead of
+/// redoing the TLS/TCP handshake for every single one. Per-request timeouts are no longer baked
+/// into the client (a client-level timeout would apply to every request alike); callers pass the
+/// adaptive timeout into [`crate::apis::client::HttpLLMClient::send`] instead, which sets it on
+/// the `RequestBuilder`. [`request`] sets no timeout of its own.
+fn build_shared_client() -> reqwest::Client {
+    reqwest::Client::builder()
         .no_proxy()
-        .build()?
-        .post(endpoint)
-        .body(json_body)
-        .header("Content-Type", "application/json")
-        .send()
-        .await?)
+        .build()
+        .expect("failed to build shared reqwest client")
 }
 
 #[allow(dead_code)]
-async fn request_with_timeout(
+async fn request(
+    client: &reqwest::Client,
     endpoint: &str,
     json_body: String,
-    timeout: Duration,
+    auth: &EndpointAuth,
 ) -> Result<Response, reqwest::Error> {
-    Ok(reqwest::Client::builder()
-        .no_proxy()
-        .timeout(timeout)
-        .build()?
+    Ok(client
         .post(endpoint)
+        .headers(auth.to_header_map())
         .body(json_body)
         .header("Content-Type", "application/json")
         .send()
         .await?)
 }
 
-async fn wait_all(response_receiver: flume::Receiver<JoinHandle<()>>) {
-    while let Ok(handle) = response_receiver.recv_async().await {
-        handle.await.unwrap();
+/// Record [`SyncSendOutcome`]'s attempt bookkeeping into a request's metrics map, alongside the
+/// historical `retry_count` (attempts after the first) kept for existing consumers of the JSONL
+/// output.
+fn insert_attempt_metrics(metrics: &mut BTreeMap<String, String>, outcome: &SyncSendOutcome) {
+    metrics.insert(
+        "retry_count".to_string(),
+        (outcome.attempt_count - 1).to_string(),
+    );
+    metrics.insert(
+        "attempt_count".to_string(),
+        outcome.attempt_count.to_string(),
+    );
+    metrics.insert(
+        "attempt_latencies_ms".to_string(),
+        serde_json::to_string(&outcome.attempt_latencies_ms).unwrap(),
+    );
+}
+
+/// A spawned request task together with the bits [`wait_all`] needs to report it as `aborted` if
+/// it has to be cut short.
+struct TrackedRequest {
+    handle: JoinHandle<()>,
+    data_index: usize,
+    response_sender: flume::Sender<BTreeMap<String, String>>,
+}
+
+/// Wait for every outstanding request task to finish, up to `drain_deadline` after dispatching
+/// has stopped (i.e. after `response_receiver` has been drained and closed). Anything still
+/// running past the deadline is aborted and reported with `status=aborted` instead of being
+/// silently dropped.
+async fn wait_all(response_receiver: flume::Receiver<TrackedRequest>, drain_deadline: Duration) {
+    let mut pending = Vec::new();
+    while let Ok(tracked) = response_receiver.recv_async().await {
+        pending.push(tracked);
+    }
+
+    let drain = async {
+        for tracked in &mut pending {
+            let _ = (&mut tracked.handle).await;
+        }
+    };
+
+    if tokio::time::timeout(drain_deadline, drain).await.is_err() {
+        for tracked in &pending {
+            if !tracked.handle.is_finished() {
+                tracked.handle.abort();
+                let mut metrics = BTreeMap::new();
+                metrics.insert("status".to_string(), "aborted".to_string());
+                metrics.insert("data_index".to_string(), tracked.data_index.to_string());
+                let _ = tracked.response_sender.send(metrics);
+            }
+        }
     }
 }
 
@@ -96,7 +154,11 @@ pub fn spawn_request_loop<A: 'static + LLMApi + Send>(
     token_sampler: Arc<TokenSampler>,
     interval_generator: IntervalGenerator,
     response_sender: flume::Sender<BTreeMap<String, String>>,
+    dispatch_sender: flume::Sender<()>,
     mut stopped: oneshot::Receiver<()>,
+    retry_policy: RetryPolicy,
+    drain_deadline: Duration,
+    auth: EndpointAuth,
 ) -> JoinHandle<Result<(), i32>> {
     static BASETIME: OnceLock<Instant> = OnceLock::new();
     BASETIME.get_or_init(|| Instant::now());
@@ -107,10 +169,12 @@ pub fn spawn_request_loop<A: 'static + LLMApi + Send>(
 
     let (tx, rx) = flume::unbounded();
     let handle = spawn(async move {
-        wait_all(rx).await;
+        wait_all(rx, drain_deadline).await;
         Ok(())
     });
 
+    let client = Arc::new(build_shared_client());
+
     spawn(async move {
         let mut timestamp = get_timestamp();
         let data_iter = dataset.iter();
@@ -121,41 +185,59 @@ pub fn spawn_request_loop<A: 'static + LLMApi + Send>(
             // data to move into closure
             let endpoint = endpoint.clone();
             let response_sender = response_sender.clone();
+            let tracked_response_sender = response_sender.clone();
+            let client = client.clone();
+            let auth = auth.clone();
+            let dispatch_sender = dispatch_sender.clone();
             // TODO: add new span
-            let (prompt, input_length, output_length) =
+            let (prompt, input_length, output_length, _system_metrics) =
                 dataset.inflate(data_index, token_sampler.as_ref());
 
             // parse in another coroutine
             let request_handle = spawn(async move {
+                let _ = dispatch_sender.send(());
                 let json_body = A::request_json_body(prompt, output_length);
                 let s_time = get_timestamp();
-                if let Ok(response) = request_with_timeout(
+                let timeout = Duration::from_secs(180.max((output_length as f64 * 0.4) as u64));
+                let outcome = HttpLLMClient::send_with_retry(
+                    &client,
                     endpoint.as_str(),
-                    json_body.to_string(),
-                    Duration::from_secs(180.max((output_length as f64 * 0.4) as u64)),
+                    json_body,
+                    timeout,
+                    &auth,
+                    &retry_policy,
                 )
-                .await
-                {
+                .await;
+
+                if let Ok(response) = outcome.result {
                     let e_time = get_timestamp();
 
-                    let mut metrics = A::parse_response(response);
+                    let mut metrics = A::parse_response(response).await;
                     metrics.insert("s_time".to_string(), s_time.to_string());
                     metrics.insert("e_time".to_string(), e_time.to_string());
                     metrics.insert("input_length".to_string(), input_length.to_string());
                     metrics.insert("output_length".to_string(), output_length.to_string());
+                    insert_attempt_metrics(&mut metrics, &outcome);
 
                     response_sender.send(metrics).unwrap();
                 } else {
                     tracing::error!(
-                        "Request {} timeout with input {} output {}",
+                        "Request {} failed after {} attempt(s) with input {} output {}",
                         data_index,
+                        outcome.attempt_count,
                         input_length,
                         output_length
                     );
                 }
             });
 
-            tx.send_async(request_handle).await.unwrap();
+            tx.send_async(TrackedRequest {
+                handle: request_handle,
+                data_index,
+                response_sender: tracked_response_sender,
+            })
+            .await
+            .unwrap();
             timestamp += interval_generator.interval_in_millis().round() as u64;
             let current_timestamp = get_timestamp();
             if timestamp > current_timestamp + 1 {
@@ -174,7 +256,12 @@ pub fn spawn_request_loop_with_timestamp<A: 'static + LLMApi + Send>(
     token_sampler: Arc<TokenSampler>,
     scale_factor: f64,
     response_sender: flume::Sender<BTreeMap<String, String>>,
+    dispatch_sender: flume::Sender<()>,
     broadcast_tx: broadcast::Sender<()>,
+    retry_policy: RetryPolicy,
+    drain_deadline: Duration,
+    auth: EndpointAuth,
+    rate_limiter: Option<Arc<RateLimiter>>,
 ) -> JoinHandle<Result<(), i32>> {
     static BASETIME: OnceLock<Instant> = OnceLock::new();
     static RETURNCODE: AtomicI32 = AtomicI32::new(0);
@@ -189,7 +276,7 @@ pub fn spawn_request_loop_with_timestamp<A: 'static + LLMApi + Send>(
 
     let (tx, rx) = flume::unbounded();
     let handle = spawn(async move {
-        wait_all(rx).await;
+        wait_all(rx, drain_deadline).await;
         let a = RETURNCODE.load(Ordering::Relaxed);
         if a == 0 {
             Ok(())
@@ -198,6 +285,7 @@ pub fn spawn_request_loop_with_timestamp<A: 'static + LLMApi + Send>(
         }
     });
     let mut gen_rx = broadcast_tx.subscribe();
+    let client = Arc::new(build_shared_client());
 
     spawn(async move {
         let data_iter = dataset.iter();
@@ -208,6 +296,10 @@ pub fn spawn_request_loop_with_timestamp<A: 'static + LLMApi + Send>(
             }
             let endpoint = endpoint.clone();
             let response_sender = response_sender.clone();
+            let tracked_response_sender = response_sender.clone();
+            let client = client.clone();
+            let auth = auth.clone();
+            let dispatch_sender = dispatch_sender.clone();
 
             let curr_timestamp = get_timestamp();
             let next_timestamp = ((*dataset).timestamp(data_index) as f64 / scale_factor) as u64;
@@ -216,47 +308,627 @@ pub fn spawn_request_loop_with_timestamp<A: 'static + LLMApi + Send>(
                 sleep(Duration::from_millis(next_timestamp - curr_timestamp)).await;
             }
 
-            // Do not parse in another coroutine to avoid sync/async lock contention 
-            let (prompt, input_length, output_length) =
+            if let Some(rate_limiter) = &rate_limiter {
+                rate_limiter.acquire().await;
+            }
+
+            // Do not parse in another coroutine to avoid sync/async lock contention
+            let (prompt, input_length, output_length, _system_metrics) =
                 dataset.inflate(data_index, token_sampler.as_ref());
-            
+
             let request_handle = spawn(async move {
+                let _ = dispatch_sender.send(());
                 let json_body = A::request_json_body(prompt, output_length);
                 let s_time = get_timestamp();
-                if let Ok(response) = request_with_timeout(
+                let timeout = Duration::from_secs(180.max((output_length as f64 * 0.4) as u64));
+                let outcome = HttpLLMClient::send_with_retry(
+                    &client,
                     endpoint.as_str(),
-                    json_body.to_string(),
-                    Duration::from_secs(180.max((output_length as f64 * 0.4) as u64)),
+                    json_body,
+                    timeout,
+                    &auth,
+                    &retry_policy,
                 )
-                .await
-                {
+                .await;
+
+                if let Ok(response) = outcome.result {
                     let e_time = get_timestamp();
 
-                    let mut metrics = A::parse_response(response);
+                    let mut metrics = A::parse_response(response).await;
                     metrics.insert("s_time".to_string(), s_time.to_string());
                     metrics.insert("e_time".to_string(), e_time.to_string());
                     metrics.insert("input_length".to_string(), input_length.to_string());
                     metrics.insert("output_length".to_string(), output_length.to_string());
                     metrics.insert("client_id".to_string(), data_index.to_string());
+                    insert_attempt_metrics(&mut metrics, &outcome);
 
                     response_sender.send(metrics).unwrap();
                 } else {
                     RETURNCODE.store(-1, Ordering::Release);
                     tracing::error!(
-                        "Request {} failed with input {} output {}",
+                        "Request {} failed after {} attempt(s) with input {} output {}",
                         data_index,
+                        outcome.attempt_count,
                         input_length,
                         output_length
                     );
                 }
             });
 
-            tx.send_async(request_handle).await.unwrap();
+            tx.send_async(TrackedRequest {
+                handle: request_handle,
+                data_index,
+                response_sender: tracked_response_sender,
+            })
+            .await
+            .unwrap();
         }
     });
     handle
 }
 
+/// Send requests at a closed-loop adaptive rate: instead of a fixed Gamma interval or a replayed
+/// timestamp, the inter-request interval is resampled every request from the target rate
+/// maintained by `controller` (see [`crate::adaptive::AdaptiveRateController`]), which re-tunes
+/// itself from the end-to-end latency trend of completed requests.
+///
+/// Await on the returned handle to wait for the loop to finish.
+pub fn spawn_adaptive_request_loop<A: 'static + LLMApi + Send>(
+    endpoint: String,
+    dataset: Arc<Pin<Box<dyn LLMTrace>>>,
+    token_sampler: Arc<TokenSampler>,
+    cv: f64,
+    controller: Arc<AdaptiveRateController>,
+    response_sender: flume::Sender<BTreeMap<String, String>>,
+    dispatch_sender: flume::Sender<()>,
+    mut stopped: oneshot::Receiver<()>,
+    retry_policy: RetryPolicy,
+    drain_deadline: Duration,
+    auth: EndpointAuth,
+) -> JoinHandle<Result<(), i32>> {
+    static BASETIME: OnceLock<Instant> = OnceLock::new();
+    BASETIME.get_or_init(|| Instant::now());
+
+    fn get_timestamp() -> u64 {
+        BASETIME.get().unwrap().elapsed().as_millis() as u64
+    }
+
+    let (tx, rx) = flume::unbounded();
+    let handle = spawn(async move {
+        wait_all(rx, drain_deadline).await;
+        Ok(())
+    });
+
+    let client = Arc::new(build_shared_client());
+
+    spawn(async move {
+        let data_iter = dataset.iter();
+        for data_index in data_iter {
+            if stopped.try_recv().is_ok() {
+                break;
+            }
+            let endpoint = endpoint.clone();
+            let response_sender = response_sender.clone();
+            let tracked_response_sender = response_sender.clone();
+            let client = client.clone();
+            let auth = auth.clone();
+            let controller = controller.clone();
+            let dispatch_sender = dispatch_sender.clone();
+            let (prompt, input_length, output_length, _system_metrics) =
+                dataset.inflate(data_index, token_sampler.as_ref());
+
+            let request_handle = spawn(async move {
+                let _ = dispatch_sender.send(());
+                let json_body = A::request_json_body(prompt, output_length);
+                let s_time = get_timestamp();
+                let timeout = Duration::from_secs(180.max((output_length as f64 * 0.4) as u64));
+                let outcome = HttpLLMClient::send_with_retry(
+                    &client,
+                    endpoint.as_str(),
+                    json_body,
+                    timeout,
+                    &auth,
+                    &retry_policy,
+                )
+                .await;
+
+                if let Ok(response) = outcome.result {
+                    let e_time = get_timestamp();
+                    controller.observe((e_time - s_time) as f64 / 1000.0);
+
+                    let mut metrics = A::parse_response(response).await;
+                    metrics.insert("s_time".to_string(), s_time.to_string());
+                    metrics.insert("e_time".to_string(), e_time.to_string());
+                    metrics.insert("input_length".to_string(), input_length.to_string());
+                    metrics.insert("output_length".to_string(), output_length.to_string());
+                    insert_attempt_metrics(&mut metrics, &outcome);
+                    metrics.insert(
+                        "target_rate".to_string(),
+                        controller.target_rate().to_string(),
+                    );
+
+                    response_sender.send(metrics).unwrap();
+                } else {
+                    tracing::error!(
+                        "Request {} failed after {} attempt(s) with input {} output {}",
+                        data_index,
+                        outcome.attempt_count,
+                        input_length,
+                        output_length
+                    );
+                }
+            });
+
+            tx.send_async(TrackedRequest {
+                handle: request_handle,
+                data_index,
+                response_sender: tracked_response_sender,
+            })
+            .await
+            .unwrap();
+
+            let mean_interval_ms = 1000.0 / controller.target_rate();
+            let interval = Gamma::new(mean_interval_ms, cv).generate().max(0.0) as u64;
+            sleep(Duration::from_millis(interval)).await;
+        }
+    });
+    handle
+}
+
+/// Same replay-timestamp driving loop as [`spawn_request_loop_with_timestamp`], but for a
+/// [`crate::protocols::Protocol`] implementor instead of an [`LLMApi`] one: the request body is
+/// sized from `dataset.inflate`'s token counts directly (no prompt text needed, since `Protocol`
+/// owns its own tokenizer) and sent via [`crate::protocols::AsyncProtocol::send`], which already
+/// retries per `retry_policy` and folds `attempts`/`wall_time_ms` into the returned metrics.
+///
+/// Await on the returned handle to wait for the loop to finish.
+pub fn spawn_protocol_request_loop_with_timestamp<P: 'static + crate::protocols::Protocol + Send + Sync>(
+    endpoint: String,
+    dataset: Arc<Pin<Box<dyn LLMTrace>>>,
+    token_sampler: Arc<TokenSampler>,
+    protocol: Arc<P>,
+    scale_factor: f64,
+    response_sender: flume::Sender<BTreeMap<String, String>>,
+    dispatch_sender: flume::Sender<()>,
+    broadcast_tx: broadcast::Sender<()>,
+    retry_policy: RetryPolicy,
+    drain_deadline: Duration,
+    rate_limiter: Option<Arc<RateLimiter>>,
+) -> JoinHandle<Result<(), i32>> {
+    static BASETIME: OnceLock<Instant> = OnceLock::new();
+    static RETURNCODE: AtomicI32 = AtomicI32::new(0);
+    BASETIME.get_or_init(|| Instant::now());
+    fn get_timestamp() -> u64 {
+        BASETIME.get().unwrap().elapsed().as_millis() as u64
+    }
+
+    let rr = dataset.rps();
+    println!("Origin request rate: {:.3} req/s", rr);
+    println!("Scaled request rate: {:.3} req/s", rr * scale_factor);
+
+    let (tx, rx) = flume::unbounded();
+    let handle = spawn(async move {
+        wait_all(rx, drain_deadline).await;
+        let a = RETURNCODE.load(Ordering::Relaxed);
+        if a == 0 {
+            Ok(())
+        } else {
+            Err(a)
+        }
+    });
+    let mut gen_rx = broadcast_tx.subscribe();
+    let client = Arc::new(build_shared_client());
+
+    spawn(async move {
+        let data_iter = dataset.iter();
+        let endpoint = Arc::new(endpoint);
+        for data_index in data_iter {
+            if gen_rx.try_recv().is_ok() {
+                break;
+            }
+            let endpoint = endpoint.clone();
+            let response_sender = response_sender.clone();
+            let tracked_response_sender = response_sender.clone();
+            let client = client.clone();
+            let protocol = protocol.clone();
+            let dispatch_sender = dispatch_sender.clone();
+
+            let curr_timestamp = get_timestamp();
+            let next_timestamp = ((*dataset).timestamp(data_index) as f64 / scale_factor) as u64;
+
+            if next_timestamp > curr_timestamp + 1 {
+                sleep(Duration::from_millis(next_timestamp - curr_timestamp)).await;
+            }
+
+            if let Some(rate_limiter) = &rate_limiter {
+                rate_limiter.acquire().await;
+            }
+
+            // Do not parse in another coroutine to avoid sync/async lock contention
+            let (_prompt, input_length, output_length, _system_metrics) =
+                dataset.inflate(data_index, token_sampler.as_ref());
+
+            let request_handle = spawn(async move {
+                let _ = dispatch_sender.send(());
+                let s_time = get_timestamp();
+
+                use crate::protocols::AsyncProtocol;
+                let result = protocol
+                    .send(
+                        &client,
+                        endpoint.as_str(),
+                        input_length,
+                        output_length,
+                        &retry_policy,
+                    )
+                    .await;
+
+                match result {
+                    Ok(mut metrics) => {
+                        let e_time = get_timestamp();
+                        metrics.insert("s_time".to_string(), s_time.to_string());
+                        metrics.insert("e_time".to_string(), e_time.to_string());
+                        metrics.insert("input_length".to_string(), input_length.to_string());
+                        metrics.insert("output_length".to_string(), output_length.to_string());
+                        metrics.insert("client_id".to_string(), data_index.to_string());
+
+                        response_sender.send(metrics).unwrap();
+                    }
+                    Err(err) => {
+                        RETURNCODE.store(-1, Ordering::Release);
+                        tracing::error!(
+                            "Request {} failed with input {} output {}: {}",
+                            data_index,
+                            input_length,
+                            output_length,
+                            err
+                        );
+                    }
+                }
+            });
+
+            tx.send_async(TrackedRequest {
+                handle: request_handle,
+                data_index,
+                response_sender: tracked_response_sender,
+            })
+            .await
+            .unwrap();
+        }
+    });
+    handle
+}
+
+/// Same replay-timestamp driving loop as [`spawn_protocol_request_loop_with_timestamp`], but
+/// coalesces up to `max_batch_size` consecutive dataset entries into one native-batch request via
+/// [`crate::protocols::Protocol::request_json_body_batched`]/
+/// [`crate::protocols::AsyncProtocol::send_batched`] instead of sending each individually. Only
+/// protocols that override the `*_batched` methods (currently [`crate::protocols::TgiProtocol`] and
+/// [`crate::protocols::VllmProtocol`]) support this; others panic on the first batch, same as their
+/// default `unimplemented!()`.
+///
+/// Await on the returned handle to wait for the loop to finish.
+pub fn spawn_protocol_batch_request_loop_with_timestamp<
+    P: 'static + crate::protocols::Protocol + Send + Sync,
+>(
+    endpoint: String,
+    dataset: Arc<Pin<Box<dyn LLMTrace>>>,
+    token_sampler: Arc<TokenSampler>,
+    protocol: Arc<P>,
+    scale_factor: f64,
+    max_batch_size: usize,
+    response_sender: flume::Sender<BTreeMap<String, String>>,
+    dispatch_sender: flume::Sender<()>,
+    broadcast_tx: broadcast::Sender<()>,
+    retry_policy: RetryPolicy,
+    drain_deadline: Duration,
+    rate_limiter: Option<Arc<RateLimiter>>,
+) -> JoinHandle<Result<(), i32>> {
+    static BASETIME: OnceLock<Instant> = OnceLock::new();
+    static RETURNCODE: AtomicI32 = AtomicI32::new(0);
+    BASETIME.get_or_init(|| Instant::now());
+    fn get_timestamp() -> u64 {
+        BASETIME.get().unwrap().elapsed().as_millis() as u64
+    }
+
+    let max_batch_size = max_batch_size.max(1);
+    let rr = dataset.rps();
+    println!("Origin request rate: {:.3} req/s", rr);
+    println!("Scaled request rate: {:.3} req/s", rr * scale_factor);
+
+    let (tx, rx) = flume::unbounded();
+    let handle = spawn(async move {
+        wait_all(rx, drain_deadline).await;
+        let a = RETURNCODE.load(Ordering::Relaxed);
+        if a == 0 {
+            Ok(())
+        } else {
+            Err(a)
+        }
+    });
+    let mut gen_rx = broadcast_tx.subscribe();
+    let client = Arc::new(build_shared_client());
+
+    spawn(async move {
+        let data_iter = dataset.iter();
+        let endpoint = Arc::new(endpoint);
+        let mut pending: Vec<(usize, u64, u64)> = Vec::with_capacity(max_batch_size);
+
+        for data_index in data_iter {
+            if gen_rx.try_recv().is_ok() {
+                break;
+            }
+
+            let curr_timestamp = get_timestamp();
+            let next_timestamp = ((*dataset).timestamp(data_index) as f64 / scale_factor) as u64;
+
+            if next_timestamp > curr_timestamp + 1 {
+                sleep(Duration::from_millis(next_timestamp - curr_timestamp)).await;
+            }
+
+            if let Some(rate_limiter) = &rate_limiter {
+                rate_limiter.acquire().await;
+            }
+
+            // Do not parse in another coroutine to avoid sync/async lock contention
+            let (_prompt, input_length, output_length, _system_metrics) =
+                dataset.inflate(data_index, token_sampler.as_ref());
+            pending.push((data_index, input_length, output_length));
+
+            if pending.len() >= max_batch_size {
+                let batch = std::mem::take(&mut pending);
+                dispatch_batch(
+                    batch,
+                    endpoint.clone(),
+                    client.clone(),
+                    protocol.clone(),
+                    response_sender.clone(),
+                    dispatch_sender.clone(),
+                    retry_policy,
+                    &tx,
+                    &RETURNCODE,
+                    *BASETIME.get().unwrap(),
+                )
+                .await;
+            }
+        }
+
+        if !pending.is_empty() {
+            dispatch_batch(
+                pending,
+                endpoint.clone(),
+                client.clone(),
+                protocol.clone(),
+                response_sender.clone(),
+                dispatch_sender.clone(),
+                retry_policy,
+                &tx,
+                &RETURNCODE,
+                *BASETIME.get().unwrap(),
+            )
+            .await;
+        }
+    });
+    handle
+}
+
+/// Spawn one batched request covering `batch` and register it with `tx` so
+/// [`spawn_protocol_batch_request_loop_with_timestamp`]'s `wait_all` drain waits for it, same as a
+/// single-request [`TrackedRequest`].
+async fn dispatch_batch<P: 'static + crate::protocols::Protocol + Send + Sync>(
+    batch: Vec<(usize, u64, u64)>,
+    endpoint: Arc<String>,
+    client: Arc<reqwest::Client>,
+    protocol: Arc<P>,
+    response_sender: flume::Sender<BTreeMap<String, String>>,
+    dispatch_sender: flume::Sender<()>,
+    retry_policy: RetryPolicy,
+    tx: &flume::Sender<TrackedRequest>,
+    returncode: &'static AtomicI32,
+    base_time: Instant,
+) {
+    let tracked_response_sender = response_sender.clone();
+    let first_data_index = batch[0].0;
+
+    let request_handle = spawn(async move {
+        let _ = dispatch_sender.send(());
+        let s_time = base_time.elapsed().as_millis() as u64;
+
+        use crate::protocols::AsyncProtocol;
+        let reqs: Vec<(u64, u64)> = batch.iter().map(|&(_, i, o)| (i, o)).collect();
+        let result = protocol
+            .send_batched(&client, endpoint.as_str(), &reqs, &retry_policy)
+            .await;
+
+        match result {
+            Ok(rows) => {
+                let e_time = base_time.elapsed().as_millis() as u64;
+                for ((data_index, input_length, output_length), mut metrics) in
+                    batch.into_iter().zip(rows)
+                {
+                    metrics.insert("s_time".to_string(), s_time.to_string());
+                    metrics.insert("e_time".to_string(), e_time.to_string());
+                    metrics.insert("input_length".to_string(), input_length.to_string());
+                    metrics.insert("output_length".to_string(), output_length.to_string());
+                    metrics.insert("client_id".to_string(), data_index.to_string());
+                    response_sender.send(metrics).unwrap();
+                }
+            }
+            Err(err) => {
+                returncode.store(-1, Ordering::Release);
+                tracing::error!("Batch of {} requests failed: {}", reqs.len(), err);
+            }
+        }
+    });
+
+    tx.send_async(TrackedRequest {
+        handle: request_handle,
+        data_index: first_data_index,
+        response_sender: tracked_response_sender,
+    })
+    .await
+    .unwrap();
+}
+
+/// Same replay-timestamp driving loop as [`spawn_request_loop_with_timestamp`], but fanning out
+/// onto a [`Dispatcher`] instead of one `tokio::spawn` per request: each request becomes a
+/// [`DispatchJob`] handed to `dispatcher` via [`Dispatcher::try_submit`], so concurrency is capped
+/// at the dispatcher's worker count and queue depth instead of growing unbounded with the arrival
+/// rate. A job dropped because the queue is full (counted in [`crate::dispatch::DispatchStats`])
+/// is logged and skipped rather than retried, the same backpressure trade `try_submit` documents.
+///
+/// `DispatchJob::run` performs the actual send synchronously from the worker thread by blocking on
+/// a handle to this Tokio runtime, per the pattern [`DispatchJob`] itself documents.
+///
+/// Await on the returned handle to wait for the loop to finish.
+pub fn spawn_dispatcher_request_loop_with_timestamp<A: 'static + LLMApi + Send>(
+    endpoint: String,
+    dataset: Arc<Pin<Box<dyn LLMTrace>>>,
+    token_sampler: Arc<TokenSampler>,
+    scale_factor: f64,
+    dispatcher: Arc<Dispatcher>,
+    response_sender: flume::Sender<BTreeMap<String, String>>,
+    dispatch_sender: flume::Sender<()>,
+    broadcast_tx: broadcast::Sender<()>,
+    retry_policy: RetryPolicy,
+    drain_deadline: Duration,
+    auth: EndpointAuth,
+    rate_limiter: Option<Arc<RateLimiter>>,
+) -> JoinHandle<Result<(), i32>> {
+    static BASETIME: OnceLock<Instant> = OnceLock::new();
+    static RETURNCODE: AtomicI32 = AtomicI32::new(0);
+    BASETIME.get_or_init(|| Instant::now());
+    fn get_timestamp() -> u64 {
+        BASETIME.get().unwrap().elapsed().as_millis() as u64
+    }
+
+    let rr = dataset.rps();
+    println!("Origin request rate: {:.3} req/s", rr);
+    println!("Scaled request rate: {:.3} req/s", rr * scale_factor);
+
+    let (tx, rx) = flume::unbounded();
+    let handle = spawn(async move {
+        wait_all(rx, drain_deadline).await;
+        let a = RETURNCODE.load(Ordering::Relaxed);
+        if a == 0 {
+            Ok(())
+        } else {
+            Err(a)
+        }
+    });
+    let mut gen_rx = broadcast_tx.subscribe();
+    let client = Arc::new(build_shared_client());
+    let runtime = tokio::runtime::Handle::current();
+
+    spawn(async move {
+        let data_iter = dataset.iter();
+        let endpoint = Arc::new(endpoint);
+        for data_index in data_iter {
+            if gen_rx.try_recv().is_ok() {
+                break;
+            }
+            let endpoint = endpoint.clone();
+            let response_sender = response_sender.clone();
+            let tracked_response_sender = response_sender.clone();
+            let client = client.clone();
+            let auth = auth.clone();
+            let dispatch_sender = dispatch_sender.clone();
+            let dispatcher = dispatcher.clone();
+            let runtime = runtime.clone();
+
+            let curr_timestamp = get_timestamp();
+            let next_timestamp = ((*dataset).timestamp(data_index) as f64 / scale_factor) as u64;
+
+            if next_timestamp > curr_timestamp + 1 {
+                sleep(Duration::from_millis(next_timestamp - curr_timestamp)).await;
+            }
+
+            if let Some(rate_limiter) = &rate_limiter {
+                rate_limiter.acquire().await;
+            }
+
+            // Do not parse in another coroutine to avoid sync/async lock contention
+            let (prompt, input_length, output_length, _system_metrics) =
+                dataset.inflate(data_index, token_sampler.as_ref());
+
+            let request_handle = spawn(async move {
+                let _ = dispatch_sender.send(());
+
+                let (job_tx, job_rx) = flume::bounded::<BTreeMap<String, String>>(1);
+                let job = DispatchJob {
+                    data_index,
+                    output_length,
+                    response_sender: job_tx,
+                    run: Box::new(move || -> BTreeMap<String, String> {
+                        runtime.block_on(async move {
+                            let json_body = A::request_json_body(prompt, output_length);
+                            let s_time = get_timestamp();
+                            let timeout =
+                                Duration::from_secs(180.max((output_length as f64 * 0.4) as u64));
+                            let outcome = HttpLLMClient::send_with_retry(
+                                &client,
+                                endpoint.as_str(),
+                                json_body,
+                                timeout,
+                                &auth,
+                                &retry_policy,
+                            )
+                            .await;
+
+                            let mut metrics = BTreeMap::new();
+                            match outcome.result {
+                                Ok(response) => {
+                                    let e_time = get_timestamp();
+                                    metrics = A::parse_response(response).await;
+                                    metrics.insert("s_time".to_string(), s_time.to_string());
+                                    metrics.insert("e_time".to_string(), e_time.to_string());
+                                    metrics.insert("input_length".to_string(), input_length.to_string());
+                                    metrics.insert("output_length".to_string(), output_length.to_string());
+                                    metrics.insert("client_id".to_string(), data_index.to_string());
+                                    insert_attempt_metrics(&mut metrics, &outcome);
+                                }
+                                Err(_) => {
+                                    RETURNCODE.store(-1, Ordering::Release);
+                                    tracing::error!(
+                                        "Request {} failed after {} attempt(s) with input {} output {}",
+                                        data_index,
+                                        outcome.attempt_count,
+                                        input_length,
+                                        output_length
+                                    );
+                                    metrics.insert("status".to_string(), "error".to_string());
+                                    metrics.insert("client_id".to_string(), data_index.to_string());
+                                }
+                            }
+                            metrics
+                        })
+                    }),
+                };
+
+                if dispatcher.try_submit(job).is_err() {
+                    tracing::error!(
+                        "Request {} dropped: dispatcher queue is full",
+                        data_index
+                    );
+                    return;
+                }
+
+                if let Ok(metrics) = job_rx.recv_async().await {
+                    response_sender.send(metrics).unwrap();
+                }
+            });
+
+            tx.send_async(TrackedRequest {
+                handle: request_handle,
+                data_index,
+                response_sender: tracked_response_sender,
+            })
+            .await
+            .unwrap();
+        }
+    });
+    handle
+}
 
 pub fn spawn_request_loop_debug<A: 'static + LLMApi + Send>(
     _endpoint: String, // 保留参数，为了接口一致
@@ -265,6 +937,7 @@ pub fn spawn_request_loop_debug<A: 'static + LLMApi + Send>(
     scale_factor: f64,
     response_sender: flume::Sender<BTreeMap<String, String>>,
     broadcast_tx: broadcast::Sender<()>,
+    drain_deadline: Duration,
 ) -> JoinHandle<Result<(), i32>> {
     use std::time::Instant;
     static BASETIME: OnceLock<Instant> = OnceLock::new();
@@ -284,7 +957,7 @@ pub fn spawn_request_loop_debug<A: 'static + LLMApi + Send>(
 
     let (tx, rx) = flume::unbounded();
     let handle = spawn(async move {
-        wait_all(rx).await;
+        wait_all(rx, drain_deadline).await;
         let a = RETURNCODE.load(Ordering::Relaxed);
         if a == 0 {
             Ok(())
@@ -304,6 +977,7 @@ pub fn spawn_request_loop_debug<A: 'static + LLMApi + Send>(
             }
             let tokenizer = validate_tokenizer.clone();
             let response_sender = response_sender.clone();
+            let tracked_response_sender = response_sender.clone();
 
             let curr_timestamp = get_timestamp();
             // milisecond
@@ -328,7 +1002,7 @@ pub fn spawn_request_loop_debug<A: 'static + LLMApi + Send>(
                 if validate_len != input_length as usize {
                     tracing::error!("Validation error: {input_length} :> {validate_len}");
                 }
-                
+
                 let mut metrics = BTreeMap::new();
                 metrics.insert("chat_id".to_string(), data_index.to_string());
                 metrics.insert("input_length".to_string(), input_length.to_string());
@@ -339,7 +1013,13 @@ pub fn spawn_request_loop_debug<A: 'static + LLMApi + Send>(
                 response_sender.send(metrics).unwrap();
             });
 
-            tx.send_async(request_handle).await.unwrap();
+            tx.send_async(TrackedRequest {
+                handle: request_handle,
+                data_index,
+                response_sender: tracked_response_sender,
+            })
+            .await
+            .unwrap();
         }
     });
 
@@ -362,6 +1042,82 @@ pub async fn report_loop(
     }
 }
 
+/// Same as [`report_loop`], but also feeds every completed request (and, via `dispatch_receiver`,
+/// every just-dispatched one — see [`crate::live_metrics::LiveMetrics::record_dispatch`]) into
+/// `live_metrics` and serves it over HTTP at `admin_addr` (`/metrics` in Prometheus text format,
+/// `/stats` as JSON) for the duration of the run. The admin server is torn down once
+/// `response_receiver` closes.
+pub async fn report_loop_with_admin(
+    output_jsonl_file: File,
+    response_receiver: flume::Receiver<BTreeMap<String, String>>,
+    dispatch_receiver: flume::Receiver<()>,
+    admin_addr: std::net::SocketAddr,
+) {
+    let live_metrics = crate::live_metrics::new_shared();
+    let admin_handle = crate::live_metrics::spawn_admin_server(admin_addr, live_metrics.clone());
+
+    let mut output_jsonl_file = output_jsonl_file;
+    let mut buf_writer = BufWriter::new(&mut output_jsonl_file);
+    loop {
+        tokio::select! {
+            metrics = response_receiver.recv_async() => {
+                let Ok(metrics) = metrics else { break };
+                live_metrics.lock().unwrap().record(&metrics);
+
+                let line = serde_json::to_string(&metrics).unwrap();
+                buf_writer.write_all(line.as_bytes()).await.unwrap();
+                buf_writer.write_all(b"\n").await.unwrap();
+                buf_writer.flush().await.unwrap();
+            }
+            dispatch = dispatch_receiver.recv_async() => {
+                if dispatch.is_ok() {
+                    live_metrics.lock().unwrap().record_dispatch();
+                }
+            }
+        }
+    }
+
+    admin_handle.abort();
+}
+
+/// Same as [`report_loop`], but also feeds every completed request (and, via `dispatch_receiver`,
+/// every just-dispatched one) into a [`crate::live_metrics`] instance rendered in place by
+/// [`crate::tui::run_dashboard`] for the duration of the run. The dashboard is torn down once
+/// `response_receiver` closes.
+pub async fn report_loop_with_tui(
+    output_jsonl_file: File,
+    response_receiver: flume::Receiver<BTreeMap<String, String>>,
+    dispatch_receiver: flume::Receiver<()>,
+) {
+    let live_metrics = crate::live_metrics::new_shared();
+    let (stop_tui_tx, stop_tui_rx) = oneshot::channel();
+    let dashboard_handle = spawn(crate::tui::run_dashboard(live_metrics.clone(), stop_tui_rx));
+
+    let mut output_jsonl_file = output_jsonl_file;
+    let mut buf_writer = BufWriter::new(&mut output_jsonl_file);
+    loop {
+        tokio::select! {
+            metrics = response_receiver.recv_async() => {
+                let Ok(metrics) = metrics else { break };
+                live_metrics.lock().unwrap().record(&metrics);
+
+                let line = serde_json::to_string(&metrics).unwrap();
+                buf_writer.write_all(line.as_bytes()).await.unwrap();
+                buf_writer.write_all(b"\n").await.unwrap();
+                buf_writer.flush().await.unwrap();
+            }
+            dispatch = dispatch_receiver.recv_async() => {
+                if dispatch.is_ok() {
+                    live_metrics.lock().unwrap().record_dispatch();
+                }
+            }
+        }
+    }
+
+    let _ = stop_tui_tx.send(());
+    let _ = dashboard_handle.await;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -369,8 +1125,8 @@ mod tests {
         dataset::{BailianDataset, LLMTrace},
         token_sampler::TokenSampler,
     };
-    use tokenizers::Tokenizer;
     use std::sync::Arc;
+    use tokenizers::Tokenizer;
     use tokio::fs::File;
 
     #[tokio::test]
@@ -383,17 +1139,18 @@ mod tests {
 
         // ====== 准备 dataset ======
         let mut dataset = BailianDataset::new();
-        dataset.load("/Users/zdy/Workspace/Rust/request-sim/data/qwen-bailian-usagetraces-anon-main/qwen_traceA_blksz_16.jsonl"); // 你要准备一个小的测试文件
+        dataset.load("/Users/zdy/Workspace/Rust/request-sim/data/qwen-bailian-usagetraces-anon-main/qwen_traceA_blksz_16.jsonl").unwrap(); // 你要准备一个小的测试文件
 
         let dataset = Arc::new(Box::pin(dataset) as Pin<Box<dyn LLMTrace>>);
 
         // ====== 准备 TokenSampler ======
         let token_sampler = Arc::new(TokenSampler::new(
-            Tokenizer::from_file("/Users/zdy/Workspace/Rust/request-sim/data/tokenizer.json").unwrap(),
+            Tokenizer::from_file("/Users/zdy/Workspace/Rust/request-sim/data/tokenizer.json")
+                .unwrap(),
             "/Users/zdy/Workspace/Rust/request-sim/data/tokenizer_config.json".to_string(),
-            4,     // num_producer
-            128,   // capacity
-            16,    // block size
+            4,   // num_producer
+            128, // capacity
+            16,  // block size
         ));
 
         // ====== 准备输出通道 ======
@@ -403,7 +1160,8 @@ mod tests {
 
         // ====== 测试循环 ======
         let iter = dataset.iter();
-        for index in iter.take(10) { // 只测前10条
+        for index in iter.take(10) {
+            // 只测前10条
             let start = std::time::Instant::now();
             let (_prompt, input_len, output_len) = dataset.inflate(index, &token_sampler);
             let elapsed_us = start.elapsed().as_micros() as u64;
@@ -0,0 +1,198 @@
+//! Declarative, panic-free header-to-metric conversion for `Protocol::parse_response`
+//! implementations that read server-reported `x-*` timing headers.
+//!
+//! A single missing or malformed header used to mean `.unwrap()` killing the whole run;
+//! [`ResponseSchema`] instead records the failure in the returned map's `errors` field and lets
+//! the caller decide per-field whether that's fatal (via [`FieldSpec::required`]).
+use std::collections::BTreeMap;
+
+use chrono::NaiveDateTime;
+use reqwest::header::HeaderMap;
+
+/// How a raw header string is turned into the value stored under its field name.
+#[derive(Debug, Clone)]
+pub enum Conversion {
+    /// Parse as `f64`, stored as-is.
+    Float,
+    /// Parse as `i64`, stored as-is.
+    Integer,
+    /// Parse as seconds (`f64`) and store as milliseconds.
+    DurationMillis,
+    /// Parse as `u64` byte count, stored as-is.
+    Bytes,
+    /// Parse with the given `chrono` format string and store as epoch milliseconds.
+    TimestampFmt(String),
+}
+
+impl Conversion {
+    fn convert(&self, raw: &str) -> Result<String, String> {
+        match self {
+            Conversion::Float => raw
+                .parse::<f64>()
+                .map(|value| value.to_string())
+                .map_err(|err| err.to_string()),
+            Conversion::Integer => raw
+                .parse::<i64>()
+                .map(|value| value.to_string())
+                .map_err(|err| err.to_string()),
+            Conversion::DurationMillis => raw
+                .parse::<f64>()
+                .map(|seconds| (seconds * 1000.0).to_string())
+                .map_err(|err| err.to_string()),
+            Conversion::Bytes => raw
+                .parse::<u64>()
+                .map(|value| value.to_string())
+                .map_err(|err| err.to_string()),
+            Conversion::TimestampFmt(fmt) => NaiveDateTime::parse_from_str(raw, fmt)
+                .map(|timestamp| timestamp.and_utc().timestamp_millis().to_string())
+                .map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// One schema entry: which header to read, how to convert it, and what to do when it's absent.
+#[derive(Debug, Clone)]
+pub struct FieldSpec {
+    header: String,
+    conversion: Conversion,
+    /// Missing the header is recorded as an error when `true`; silently skipped otherwise.
+    required: bool,
+    /// Value to fall back to (before conversion) when the header is missing or fails to convert.
+    default: Option<String>,
+}
+
+/// A declarative map of output field name -> header to read and how to convert it, applied by
+/// [`ResponseSchema::apply`] instead of a chain of `.unwrap()` calls.
+#[derive(Debug, Clone, Default)]
+pub struct ResponseSchema {
+    fields: Vec<(String, FieldSpec)>,
+}
+
+impl ResponseSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a required field: missing or unparseable headers are recorded in `errors`.
+    pub fn field(mut self, name: impl Into<String>, header: impl Into<String>, conversion: Conversion) -> Self {
+        self.fields.push((
+            name.into(),
+            FieldSpec {
+                header: header.into(),
+                conversion,
+                required: true,
+                default: None,
+            },
+        ));
+        self
+    }
+
+    /// Add an optional field: a missing header is skipped silently (or falls back to `default`),
+    /// and only an unparseable header is recorded as an error.
+    pub fn optional_field(
+        mut self,
+        name: impl Into<String>,
+        header: impl Into<String>,
+        conversion: Conversion,
+        default: impl Into<String>,
+    ) -> Self {
+        self.fields.push((
+            name.into(),
+            FieldSpec {
+                header: header.into(),
+                conversion,
+                required: false,
+                default: Some(default.into()),
+            },
+        ));
+        self
+    }
+
+    /// Apply every field's conversion against `headers`, inserting converted values into `map`
+    /// and appending any failures to a single semicolon-joined `errors` entry.
+    pub fn apply(&self, headers: &HeaderMap, map: &mut BTreeMap<String, String>) {
+        let mut errors = Vec::new();
+        for (name, spec) in &self.fields {
+            match headers.get(&spec.header).and_then(|value| value.to_str().ok()) {
+                Some(raw) => match spec.conversion.convert(raw) {
+                    Ok(value) => {
+                        map.insert(name.clone(), value);
+                    }
+                    Err(err) => {
+                        errors.push(format!("{name}: {err}"));
+                        if let Some(default) = &spec.default {
+                            map.insert(name.clone(), default.clone());
+                        }
+                    }
+                },
+                None => {
+                    if spec.required {
+                        errors.push(format!("{name}: missing header '{}'", spec.header));
+                    }
+                    if let Some(default) = &spec.default {
+                        map.insert(name.clone(), default.clone());
+                    }
+                }
+            }
+        }
+        if !errors.is_empty() {
+            map.insert("errors".to_string(), errors.join("; "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderName, HeaderValue};
+
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        headers
+    }
+
+    #[test]
+    fn converts_known_headers() {
+        let schema = ResponseSchema::new()
+            .field("total_time", "x-total-time", Conversion::Float)
+            .field("output_length", "x-output-length", Conversion::Integer);
+        let mut map = BTreeMap::new();
+        schema.apply(&headers(&[("x-total-time", "1.5"), ("x-output-length", "42")]), &mut map);
+
+        assert_eq!(map.get("total_time"), Some(&"1.5".to_string()));
+        assert_eq!(map.get("output_length"), Some(&"42".to_string()));
+        assert!(!map.contains_key("errors"));
+    }
+
+    #[test]
+    fn records_missing_required_header_instead_of_panicking() {
+        let schema = ResponseSchema::new().field("total_time", "x-total-time", Conversion::Float);
+        let mut map = BTreeMap::new();
+        schema.apply(&headers(&[]), &mut map);
+
+        assert!(!map.contains_key("total_time"));
+        assert!(map.get("errors").unwrap().contains("x-total-time"));
+    }
+
+    #[test]
+    fn optional_field_falls_back_to_default_when_missing() {
+        let schema = ResponseSchema::new().optional_field(
+            "queue_time",
+            "x-queue-time",
+            Conversion::Float,
+            "0",
+        );
+        let mut map = BTreeMap::new();
+        schema.apply(&headers(&[]), &mut map);
+
+        assert_eq!(map.get("queue_time"), Some(&"0".to_string()));
+        assert!(!map.contains_key("errors"));
+    }
+}
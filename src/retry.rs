@@ -0,0 +1,90 @@
+//! Retry-with-backoff policy for the request loops in [`crate::requester`].
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::StatusCode;
+
+/// Controls whether, and how, a failed request is retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one. `1` disables retrying.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub base_delay: Duration,
+    /// Multiplier applied to the delay after every retry.
+    pub backoff_factor: f64,
+    /// Upper bound on the backoff delay before jitter, regardless of `backoff_factor`; the +/-20%
+    /// jitter in [`Self::delay_for_attempt`] can take the actual wait up to 1.2x this.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            backoff_factor: 2.0,
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries; equivalent to the loops' historical behavior.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Delay to wait before retry attempt number `attempt` (1-indexed: the first retry is `1`),
+    /// with +/-20% jitter so a burst of simultaneously-failing requests doesn't retry in lockstep.
+    pub fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        let unjittered = self.base_delay.as_secs_f64() * self.backoff_factor.powi(attempt as i32 - 1);
+        let unjittered = unjittered.min(self.max_delay.as_secs_f64());
+        let jitter = rand::thread_rng().gen_range(0.8..1.2);
+        Duration::from_secs_f64(unjittered * jitter)
+    }
+
+    /// Whether a transport-level error (connect failure, timeout) should be retried.
+    pub fn is_retryable_error(&self, err: &reqwest::Error) -> bool {
+        err.is_timeout() || err.is_connect()
+    }
+
+    /// Whether an HTTP response status should be treated as a transient, retryable failure.
+    pub fn is_retryable_status(&self, status: StatusCode) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+/// Outcome of running a request through [`RetryPolicy`], reported back into the metrics map.
+pub struct RetryOutcome<T> {
+    pub result: Result<T, reqwest::Error>,
+    pub retry_count: u32,
+}
+
+/// Run `attempt` (a single request) up to `policy.max_attempts` times, retrying on timeouts,
+/// connect errors, and any `Ok` value for which `is_retryable_ok` returns `false` (the caller's
+/// choice of retryable status codes).
+pub async fn with_retry<F, Fut, T>(policy: &RetryPolicy, is_retryable_ok: impl Fn(&T) -> bool, mut attempt: F) -> RetryOutcome<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, reqwest::Error>>,
+{
+    let mut retry_count = 0;
+    loop {
+        let result = attempt().await;
+        let should_retry = match &result {
+            Ok(value) => !is_retryable_ok(value),
+            Err(err) => policy.is_retryable_error(err),
+        };
+
+        if !should_retry || retry_count + 1 >= policy.max_attempts {
+            return RetryOutcome { result, retry_count };
+        }
+
+        retry_count += 1;
+        tokio::time::sleep(policy.delay_for_attempt(retry_count)).await;
+    }
+}
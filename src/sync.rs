@@ -0,0 +1,17 @@
+//! Thin indirection so [`crate::SpinLock`]/[`crate::SpinRwLock`] can run against either real
+//! atomics or `loom`'s model-checked ones, selected by the `loom` cfg flag (`--cfg loom`); the
+//! `loom` cargo feature alone does not switch them. The lock implementations only ever go through
+//! `crate::sync::atomic`/`crate::sync::yield_now`, never
+//! `std::sync::atomic`/`std::thread::yield_now` directly, so
+//! `RUSTFLAGS="--cfg loom" cargo test --features loom --lib` can explore every interleaving
+//! `loom::model` generates for the `#[cfg(loom)]` tests at the bottom of `lib.rs`.
+
+#[cfg(not(loom))]
+pub(crate) use std::sync::atomic;
+#[cfg(not(loom))]
+pub(crate) use std::thread::yield_now;
+
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic;
+#[cfg(loom)]
+pub(crate) use loom::thread::yield_now;
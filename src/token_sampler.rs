@@ -12,14 +12,21 @@ use std::{
 };
 use tokenizers::Tokenizer;
 
+use crate::SpinRwLock;
+
 /// TokenSampler: 带异步采样与缓存机制的随机文本块生成器
 pub struct TokenSampler {
     tokenizer: Tokenizer,
     vocab_size: u32,
     splitter: Vec<String>,
     block_size: u32,
-    receiver: Arc<Mutex<channel::Receiver<String>>>,
-    ragged_block_cache: Arc<Mutex<HashMap<usize, channel::Receiver<String>>>>,
+    /// `crossbeam::channel::Receiver` is already a cheap-to-clone, thread-safe MPMC handle, so
+    /// every consumer calls `recv` on this shared receiver through `&self` instead of serializing
+    /// behind a `Mutex`.
+    receiver: channel::Receiver<String>,
+    /// Populated once during warmup (one entry per ragged block size) and only ever read from
+    /// after that, so consumers take the read path and never contend with each other.
+    ragged_block_cache: SpinRwLock<HashMap<usize, channel::Receiver<String>>>,
     notify_tx: channel::Sender<usize>,
     ragged_block_sender: Arc<Mutex<HashMap<usize, channel::Sender<String>>>>,
 }
@@ -47,15 +54,14 @@ impl TokenSampler {
 
         let splitter = Self::resolve_splitter(&json);
 
-        let ragged_block_cache: Arc<Mutex<HashMap<usize, channel::Receiver<String>>>> =
-            Arc::new(Mutex::new(HashMap::new()));
+        let mut ragged_block_cache_init: HashMap<usize, channel::Receiver<String>> =
+            HashMap::new();
         let ragged_block_sender: Arc<Mutex<HashMap<usize, channel::Sender<String>>>> =
             Arc::new(Mutex::new(HashMap::new()));
         let (notify_tx, notify_rx) = channel::unbounded::<usize>();
 
         // 有界通道
         let (tx, rx) = channel::bounded::<String>(channel_capacity);
-        let rx_arc = Arc::new(Mutex::new(rx));
 
         // 启动生产者线程
         for i in 0..num_producers {
@@ -87,7 +93,7 @@ impl TokenSampler {
                 tx.send(prompt).unwrap();
             }
             ragged_block_sender.lock().unwrap().insert(i as usize, tx);
-            ragged_block_cache.lock().unwrap().insert(i as usize, rx);
+            ragged_block_cache_init.insert(i as usize, rx);
         }
         tracing::info!("Warmup finished!");
 
@@ -96,8 +102,8 @@ impl TokenSampler {
             vocab_size,
             splitter,
             block_size,
-            receiver: rx_arc,
-            ragged_block_cache,
+            receiver: rx,
+            ragged_block_cache: SpinRwLock::new(ragged_block_cache_init),
             notify_tx,
             ragged_block_sender,
         }
@@ -236,12 +242,15 @@ impl TokenSampler {
         // 尝试从 channel 中获取
 
         if self.block_size == n as u32 {
-            if let Ok(sample) = self.receiver.lock().unwrap().recv() {
+            if let Ok(sample) = self.receiver.recv() {
                 return sample;
             }
         }
 
-        if let Some(rx) = self.ragged_block_cache.lock().unwrap().get(&n) {
+        // 读多写少：缓存在 warmup 阶段一次性填充完毕，之后只有读者，read_lock 之间互不阻塞。
+        let rx = self.ragged_block_cache.read_lock().get(&n).cloned();
+
+        if let Some(rx) = rx {
             if let Ok(sample) = rx.try_recv() {
                 self.notify_tx.send(n).unwrap();
                 return sample;
@@ -369,4 +378,58 @@ mod tests {
         println!("--------------------------------");
         println!("Speed: {:<4}ms/block | block size: {stride}", total_elapsed / total_cnt as f64);
     }
+
+    /// 测量 `gen_string` 的消费路径在去掉 `Mutex<Receiver>` 之后随消费者线程数变化的吞吐，
+    /// 用于对比加锁版本的串行化；只打印结果，不做断言。每个线程数配置跑固定时长（500ms），
+    /// 统计完成的 `gen_string` 调用数。
+    ///
+    /// 输出格式：
+    /// ```
+    /// threads  | ops        | ops/sec
+    /// 1        | 1234       | 2468.0
+    /// 2        | 2400       | 4800.0
+    /// ...
+    /// ```
+    #[test]
+    fn bench_gen_string_consumer_scaling() {
+        let tokenizer_path = "data/tokenizer.json";
+        let tokenizer = Tokenizer::from_file(tokenizer_path).expect("Failed to load tokenizer");
+        let tokenizer_config_path = "data/tokenizer_config.json".to_string();
+
+        let block_size = 16;
+        let sampler = Arc::new(TokenSampler::new(
+            tokenizer,
+            tokenizer_config_path,
+            4,
+            256,
+            block_size,
+        ));
+
+        println!("==== TokenSampler consumer scaling bench ====");
+        println!("{:<8} | {:<10} | {:<10}", "threads", "ops", "ops/sec");
+        println!("--------------------------------");
+
+        let run_duration = Duration::from_millis(500);
+        for &num_threads in &[1usize, 2, 4, 8] {
+            let handles: Vec<_> = (0..num_threads)
+                .map(|_| {
+                    let sampler = Arc::clone(&sampler);
+                    thread::spawn(move || {
+                        let start = Instant::now();
+                        let mut ops = 0u64;
+                        while start.elapsed() < run_duration {
+                            let _ = sampler.gen_string(block_size as usize);
+                            ops += 1;
+                        }
+                        ops
+                    })
+                })
+                .collect();
+
+            let total_ops: u64 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+            let ops_per_sec = total_ops as f64 / run_duration.as_secs_f64();
+            println!("{:<8} | {:<10} | {:<10.1}", num_threads, total_ops, ops_per_sec);
+        }
+        println!("--------------------------------");
+    }
 }
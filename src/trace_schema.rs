@@ -0,0 +1,281 @@
+//! Declarative, panic-free row-to-field conversion for [`crate::dataset::GenericTrace`], the
+//! config-driven trace loader.
+//!
+//! Mirrors [`crate::response_schema`]'s shape (a schema of named fields, each with its own
+//! conversion and required-ness, applied without ever `.unwrap()`-ing a malformed value) but for
+//! trace rows (CSV cells / JSONL fields) instead of HTTP headers.
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+/// How a raw trace-row string is turned into the value stored under its field name.
+#[derive(Debug, Clone)]
+pub enum Conversion {
+    /// Parse as `i64`, stored as-is.
+    Integer,
+    /// Parse as `f64`, stored as-is.
+    Float,
+    /// Passed through unchanged.
+    Bytes,
+    /// Parse as an epoch timestamp and normalize to milliseconds. Seconds vs. milliseconds is
+    /// auto-detected by magnitude: a value `>= 10^12` is assumed to already be milliseconds.
+    Timestamp,
+    /// Parse with the given `chrono::NaiveDateTime` format string (e.g.
+    /// `"%Y-%m-%d %H:%M:%S%.f"`) and store as epoch milliseconds, assuming UTC.
+    TimestampFmt(String),
+    /// Parse with the given timezone-aware `DateTime` format string and store as epoch
+    /// milliseconds, normalized from whatever offset the value carries.
+    TimestampTZFmt(String),
+}
+
+impl Conversion {
+    fn convert(&self, raw: &str) -> Result<String, String> {
+        match self {
+            Conversion::Integer => raw
+                .parse::<i64>()
+                .map(|value| value.to_string())
+                .map_err(|err| err.to_string()),
+            Conversion::Float => raw
+                .parse::<f64>()
+                .map(|value| value.to_string())
+                .map_err(|err| err.to_string()),
+            Conversion::Bytes => Ok(raw.to_string()),
+            Conversion::Timestamp => raw
+                .parse::<i64>()
+                .map(|value| {
+                    if value.abs() >= 1_000_000_000_000 {
+                        value.to_string()
+                    } else {
+                        (value * 1000).to_string()
+                    }
+                })
+                .map_err(|err| err.to_string()),
+            Conversion::TimestampFmt(fmt) => NaiveDateTime::parse_from_str(raw, fmt)
+                .map(|timestamp| timestamp.and_utc().timestamp_millis().to_string())
+                .map_err(|err| err.to_string()),
+            Conversion::TimestampTZFmt(fmt) => DateTime::parse_from_str(raw, fmt)
+                .map(|timestamp| timestamp.timestamp_millis().to_string())
+                .map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// JSON-deserializable counterpart to [`Conversion`], so a [`TraceSchema`] can be described in a
+/// config file instead of built with [`TraceSchema::field`]/[`TraceSchema::optional_field`] calls.
+/// Unit variants serialize as a plain string (e.g. `"integer"`); the two `*_fmt` variants take
+/// their format string as the JSON value (e.g. `{"timestamp_fmt": "%Y-%m-%d %H:%M:%S%.f"}`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ConversionConfig {
+    Integer,
+    Float,
+    Bytes,
+    Timestamp,
+    TimestampFmt(String),
+    TimestampTzFmt(String),
+}
+
+impl From<ConversionConfig> for Conversion {
+    fn from(config: ConversionConfig) -> Self {
+        match config {
+            ConversionConfig::Integer => Conversion::Integer,
+            ConversionConfig::Float => Conversion::Float,
+            ConversionConfig::Bytes => Conversion::Bytes,
+            ConversionConfig::Timestamp => Conversion::Timestamp,
+            ConversionConfig::TimestampFmt(fmt) => Conversion::TimestampFmt(fmt),
+            ConversionConfig::TimestampTzFmt(fmt) => Conversion::TimestampTZFmt(fmt),
+        }
+    }
+}
+
+/// One [`TraceSchema::field`]/[`TraceSchema::optional_field`] call, as read from a schema file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FieldConfig {
+    name: String,
+    source_column: String,
+    conversion: ConversionConfig,
+    #[serde(default = "default_required")]
+    required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+/// On-disk shape read by [`TraceSchema::from_json_file`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TraceSchemaFile {
+    fields: Vec<FieldConfig>,
+}
+
+/// Why [`TraceSchema::from_json_file`] failed to load a schema file.
+#[derive(Debug)]
+pub enum TraceSchemaError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+}
+
+impl std::fmt::Display for TraceSchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceSchemaError::Io(err) => write!(f, "I/O error reading schema file: {err}"),
+            TraceSchemaError::Parse(err) => write!(f, "failed to parse schema file: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for TraceSchemaError {}
+
+impl From<std::io::Error> for TraceSchemaError {
+    fn from(err: std::io::Error) -> Self {
+        TraceSchemaError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for TraceSchemaError {
+    fn from(err: serde_json::Error) -> Self {
+        TraceSchemaError::Parse(err)
+    }
+}
+
+/// One schema entry: which source column to read, how to convert it, and what to do when it's
+/// absent.
+#[derive(Debug, Clone)]
+struct FieldSpec {
+    source_column: String,
+    conversion: Conversion,
+    /// Missing the column is recorded as an error when `true`; silently skipped otherwise.
+    required: bool,
+}
+
+/// A declarative map of logical trace field (`timestamp`, `input_length`, `output_length`,
+/// optionally `hash_ids`/`block_size`) -> concrete source column and how to convert it, applied by
+/// [`TraceSchema::apply`] so a new trace format can be onboarded by describing its columns instead
+/// of writing a new [`crate::dataset::LLMTrace`] impl.
+#[derive(Debug, Clone, Default)]
+pub struct TraceSchema {
+    fields: Vec<(String, FieldSpec)>,
+}
+
+impl TraceSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a required field: a missing or unparseable source column is recorded in `errors`.
+    pub fn field(mut self, name: impl Into<String>, source_column: impl Into<String>, conversion: Conversion) -> Self {
+        self.fields.push((
+            name.into(),
+            FieldSpec {
+                source_column: source_column.into(),
+                conversion,
+                required: true,
+            },
+        ));
+        self
+    }
+
+    /// Add an optional field: a missing source column is skipped silently, and only an
+    /// unparseable one is recorded as an error.
+    pub fn optional_field(
+        mut self,
+        name: impl Into<String>,
+        source_column: impl Into<String>,
+        conversion: Conversion,
+    ) -> Self {
+        self.fields.push((
+            name.into(),
+            FieldSpec {
+                source_column: source_column.into(),
+                conversion,
+                required: false,
+            },
+        ));
+        self
+    }
+
+    /// Build a schema from a JSON config file instead of chaining
+    /// [`field`](Self::field)/[`optional_field`](Self::optional_field) calls, so a new trace
+    /// format can be onboarded by dropping in a config file instead of a code change. See
+    /// [`TraceSchemaFile`] for the expected shape.
+    pub fn from_json_file(path: &str) -> Result<Self, TraceSchemaError> {
+        let contents = std::fs::read_to_string(path)?;
+        let file: TraceSchemaFile = serde_json::from_str(&contents)?;
+        let mut schema = TraceSchema::new();
+        for field in file.fields {
+            let conversion = Conversion::from(field.conversion);
+            schema = if field.required {
+                schema.field(field.name, field.source_column, conversion)
+            } else {
+                schema.optional_field(field.name, field.source_column, conversion)
+            };
+        }
+        Ok(schema)
+    }
+
+    /// Apply every field's conversion against `row`, inserting converted values into `out` and
+    /// appending any failures to a single semicolon-joined `errors` entry.
+    pub fn apply(&self, row: &HashMap<String, String>, out: &mut BTreeMap<String, String>) {
+        let mut errors = Vec::new();
+        for (name, spec) in &self.fields {
+            match row.get(&spec.source_column) {
+                Some(raw) => match spec.conversion.convert(raw) {
+                    Ok(value) => {
+                        out.insert(name.clone(), value);
+                    }
+                    Err(err) => errors.push(format!("{name}: {err}")),
+                },
+                None if spec.required => {
+                    errors.push(format!("{name}: missing column '{}'", spec.source_column))
+                }
+                None => {}
+            }
+        }
+        if !errors.is_empty() {
+            out.insert("errors".to_string(), errors.join("; "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn converts_known_columns() {
+        let schema = TraceSchema::new()
+            .field("timestamp", "ts", Conversion::Timestamp)
+            .field("input_length", "in_len", Conversion::Integer);
+        let mut out = BTreeMap::new();
+        schema.apply(&row(&[("ts", "1000"), ("in_len", "42")]), &mut out);
+
+        assert_eq!(out.get("timestamp"), Some(&"1000000".to_string()));
+        assert_eq!(out.get("input_length"), Some(&"42".to_string()));
+        assert!(!out.contains_key("errors"));
+    }
+
+    #[test]
+    fn records_missing_required_column_instead_of_panicking() {
+        let schema = TraceSchema::new().field("timestamp", "ts", Conversion::Timestamp);
+        let mut out = BTreeMap::new();
+        schema.apply(&row(&[]), &mut out);
+
+        assert!(!out.contains_key("timestamp"));
+        assert!(out.get("errors").unwrap().contains("ts"));
+    }
+
+    #[test]
+    fn optional_column_is_skipped_silently_when_missing() {
+        let schema = TraceSchema::new().optional_field("hash_ids", "hash_ids", Conversion::Bytes);
+        let mut out = BTreeMap::new();
+        schema.apply(&row(&[]), &mut out);
+
+        assert!(!out.contains_key("hash_ids"));
+        assert!(!out.contains_key("errors"));
+    }
+}
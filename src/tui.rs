@@ -0,0 +1,107 @@
+//! Optional crossterm-based live dashboard for a running `report_loop`, gated behind `--tui`.
+//!
+//! Polls the same [`SharedLiveMetrics`] that [`crate::live_metrics::spawn_admin_server`] serves
+//! over HTTP and redraws a summary in place every [`REDRAW_INTERVAL`], so an operator gets
+//! request rate, in-flight/completed counts, and latency percentiles (cumulative since the run
+//! started, not windowed) without having to tail `output.jsonl`.
+use std::{collections::VecDeque, io::Write, time::Duration};
+
+use crossterm::{
+    cursor, execute,
+    terminal::{Clear, ClearType},
+};
+use tokio::sync::oneshot;
+
+use crate::live_metrics::{LiveMetricsSnapshot, SharedLiveMetrics};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+const RATE_HISTORY_LEN: usize = 60;
+
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Render a rolling window of non-negative samples as a one-line sparkline.
+fn sparkline(history: &VecDeque<u64>) -> String {
+    let max = history.iter().copied().max().unwrap_or(0).max(1);
+    history
+        .iter()
+        .map(|&v| {
+            let level =
+                ((v as f64 / max as f64) * (SPARK_LEVELS.len() - 1) as f64).round() as usize;
+            SPARK_LEVELS[level]
+        })
+        .collect()
+}
+
+fn render(
+    stdout: &mut std::io::Stdout,
+    snapshot: &LiveMetricsSnapshot,
+    rps: f64,
+    history: &VecDeque<u64>,
+) {
+    let _ = execute!(stdout, cursor::MoveTo(0, 0), Clear(ClearType::All));
+    let _ = writeln!(stdout, "request-sim live dashboard (ctrl-c to stop)");
+    let _ = writeln!(
+        stdout,
+        "rate        {:>8.1} req/s  [{}]",
+        rps,
+        sparkline(history)
+    );
+    let _ = writeln!(
+        stdout,
+        "requests    total={:<8} success={:<8} error={:<8} in_flight={:<6}",
+        snapshot.request_count, snapshot.success_count, snapshot.error_count, snapshot.in_flight
+    );
+    let _ = writeln!(
+        stdout,
+        "ttft (s)       p50={:<8.3} p90={:<8.3} p99={:<8.3}",
+        snapshot.first_token_time.p50, snapshot.first_token_time.p90, snapshot.first_token_time.p99
+    );
+    let _ = writeln!(
+        stdout,
+        "e2e (s)        p50={:<8.3} p90={:<8.3} p99={:<8.3}",
+        snapshot.total_time.p50, snapshot.total_time.p90, snapshot.total_time.p99
+    );
+    let _ = writeln!(
+        stdout,
+        "queue (s)      p50={:<8.3} p90={:<8.3} p99={:<8.3}",
+        snapshot.queue_time.p50, snapshot.queue_time.p90, snapshot.queue_time.p99
+    );
+    let _ = writeln!(
+        stdout,
+        "inference (s)  p50={:<8.3} p90={:<8.3} p99={:<8.3}",
+        snapshot.inference_time.p50, snapshot.inference_time.p90, snapshot.inference_time.p99
+    );
+    let _ = stdout.flush();
+}
+
+/// Redraw loop: polls `live_metrics` every [`REDRAW_INTERVAL`] and renders it until `stopped`
+/// fires. The caller is responsible for aborting the returned handle's task if it needs to stop
+/// the dashboard some other way.
+pub async fn run_dashboard(live_metrics: SharedLiveMetrics, mut stopped: oneshot::Receiver<()>) {
+    let mut stdout = std::io::stdout();
+    let _ = execute!(stdout, cursor::Hide);
+
+    let mut rate_history = VecDeque::with_capacity(RATE_HISTORY_LEN);
+    let mut last_request_count = 0u64;
+
+    loop {
+        let snapshot = live_metrics.lock().unwrap().snapshot();
+        let delta = snapshot.request_count.saturating_sub(last_request_count);
+        last_request_count = snapshot.request_count;
+        let rps = delta as f64 / REDRAW_INTERVAL.as_secs_f64();
+
+        rate_history.push_back(delta);
+        if rate_history.len() > RATE_HISTORY_LEN {
+            rate_history.pop_front();
+        }
+
+        render(&mut stdout, &snapshot, rps, &rate_history);
+
+        tokio::select! {
+            _ = tokio::time::sleep(REDRAW_INTERVAL) => {}
+            _ = &mut stopped => break,
+        }
+    }
+
+    let _ = execute!(stdout, cursor::Show);
+}